@@ -0,0 +1,23 @@
+//! Allocation-free kernel collections (`bulldog-collections`).
+//!
+//! - `ring`: fixed-capacity ring buffers — a plain `RingBuffer` for use under
+//!   a lock, a lock-free single-producer/single-consumer `SpscRing`, and an
+//!   interrupt-safe multi-producer `MpscRing`.
+//! - `list`: an intrusive doubly linked list whose links live inside the
+//!   elements, so membership never allocates.
+//!
+//! Everything here is `const`-constructible and usable before the heap
+//! exists, from statics, and (where documented) from interrupt handlers.
+//!
+//! The kernel re-exports this crate as `kernel::collections`. It lives on
+//! its own so its unit tests run on the host (`cargo test -p bulldog-collections`).
+
+#![cfg_attr(not(test), no_std)]
+
+/// Const-generic ring buffers.
+pub mod ring;
+/// Intrusive doubly linked list.
+pub mod list;
+
+pub use list::{Link, Linked, List};
+pub use ring::{MpscRing, RingBuffer, SpscRing};
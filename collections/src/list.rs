@@ -0,0 +1,309 @@
+//! Intrusive doubly linked list (`list.rs`).
+//!
+//! - Elements embed a `Link` and implement `Linked` (usually through
+//!   `impl_linked!`) to say where it is.
+//! - `List` stores pointers to elements it does not own: pushing and
+//!   removing never allocate, and any element can be unlinked in `O(1)`.
+//! - A `Link` belongs to at most one list at a time; `Link::is_linked`
+//!   tells whether it currently does.
+//!
+//! The list has no lock of its own; guard it like any other shared state.
+//! Callers guarantee that linked elements stay put and outlive their
+//! membership, which is why the mutating operations are `unsafe`.
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+/// Per-element list hook.
+#[derive(Debug, Default)]
+pub struct Link {
+    prev: Cell<Option<NonNull<Link>>>,
+    next: Cell<Option<NonNull<Link>>>,
+    linked: Cell<bool>,
+}
+
+impl Link {
+    pub const fn new() -> Self {
+        Link { prev: Cell::new(None), next: Cell::new(None), linked: Cell::new(false) }
+    }
+
+    /// Whether the element is currently on a list.
+    pub fn is_linked(&self) -> bool {
+        self.linked.get()
+    }
+}
+
+/// Types that embed a `Link`.
+///
+/// # Safety
+/// `from_link` must invert `link`: given the `Link` of some `Self`, return
+/// a pointer to that `Self`.
+pub unsafe trait Linked {
+    fn link(&self) -> &Link;
+
+    /// Recover the element from its `Link`.
+    ///
+    /// # Safety
+    /// `link` must point to the `Link` inside a live `Self`.
+    unsafe fn from_link(link: NonNull<Link>) -> NonNull<Self>;
+}
+
+/// Implement `Linked` for a struct with a `Link` field.
+///
+/// ```ignore
+/// struct Waiter { pid: Pid, link: Link }
+/// impl_linked!(Waiter, link);
+/// ```
+#[macro_export]
+macro_rules! impl_linked {
+    ($ty:ty, $field:ident) => {
+        unsafe impl $crate::list::Linked for $ty {
+            fn link(&self) -> &$crate::list::Link {
+                &self.$field
+            }
+            unsafe fn from_link(
+                link: core::ptr::NonNull<$crate::list::Link>,
+            ) -> core::ptr::NonNull<Self> {
+                let offset = core::mem::offset_of!($ty, $field);
+                unsafe { link.byte_sub(offset).cast() }
+            }
+        }
+    };
+}
+
+/// Doubly linked list of `T`s threaded through their `Link`s.
+pub struct List<T: Linked> {
+    head: Option<NonNull<Link>>,
+    tail: Option<NonNull<Link>>,
+    len: usize,
+    _marker: PhantomData<NonNull<T>>,
+}
+
+// Safety: the list only hands out pointers; moving it between CPUs is as
+// safe as moving the elements, which must be `Send`.
+unsafe impl<T: Linked + Send> Send for List<T> {}
+
+impl<T: Linked> List<T> {
+    pub const fn new() -> Self {
+        List { head: None, tail: None, len: 0, _marker: PhantomData }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append `item`.
+    ///
+    /// # Safety
+    /// `item` must not be on any list, must not move, and must outlive its
+    /// membership.
+    pub unsafe fn push_back(&mut self, item: NonNull<T>) {
+        let link = unsafe { Self::link_of(item) };
+        assert!(!unsafe { link.as_ref() }.is_linked(), "list: element already linked");
+        unsafe {
+            link.as_ref().prev.set(self.tail);
+            link.as_ref().next.set(None);
+            link.as_ref().linked.set(true);
+            match self.tail {
+                Some(tail) => tail.as_ref().next.set(Some(link)),
+                None => self.head = Some(link),
+            }
+        }
+        self.tail = Some(link);
+        self.len += 1;
+    }
+
+    /// Prepend `item`.
+    ///
+    /// # Safety
+    /// As for `push_back`.
+    pub unsafe fn push_front(&mut self, item: NonNull<T>) {
+        let link = unsafe { Self::link_of(item) };
+        assert!(!unsafe { link.as_ref() }.is_linked(), "list: element already linked");
+        unsafe {
+            link.as_ref().prev.set(None);
+            link.as_ref().next.set(self.head);
+            link.as_ref().linked.set(true);
+            match self.head {
+                Some(head) => head.as_ref().prev.set(Some(link)),
+                None => self.tail = Some(link),
+            }
+        }
+        self.head = Some(link);
+        self.len += 1;
+    }
+
+    /// Unlink and return the first element.
+    pub fn pop_front(&mut self) -> Option<NonNull<T>> {
+        let link = self.head?;
+        unsafe {
+            self.unlink(link);
+            Some(T::from_link(link))
+        }
+    }
+
+    /// Unlink and return the last element.
+    pub fn pop_back(&mut self) -> Option<NonNull<T>> {
+        let link = self.tail?;
+        unsafe {
+            self.unlink(link);
+            Some(T::from_link(link))
+        }
+    }
+
+    /// Unlink `item` from this list.
+    ///
+    /// # Safety
+    /// `item` must be on this list (not merely on some list).
+    pub unsafe fn remove(&mut self, item: NonNull<T>) {
+        let link = unsafe { Self::link_of(item) };
+        assert!(unsafe { link.as_ref() }.is_linked(), "list: element not linked");
+        unsafe { self.unlink(link) };
+    }
+
+    /// The first element.
+    pub fn front(&self) -> Option<NonNull<T>> {
+        self.head.map(|link| unsafe { T::from_link(link) })
+    }
+
+    /// Elements front to back. The list must not change while iterating.
+    pub fn iter(&self) -> impl Iterator<Item = NonNull<T>> + '_ {
+        let mut cursor = self.head;
+        core::iter::from_fn(move || {
+            let link = cursor?;
+            cursor = unsafe { link.as_ref() }.next.get();
+            Some(unsafe { T::from_link(link) })
+        })
+    }
+
+    unsafe fn link_of(item: NonNull<T>) -> NonNull<Link> {
+        NonNull::from(unsafe { item.as_ref() }.link())
+    }
+
+    unsafe fn unlink(&mut self, link: NonNull<Link>) {
+        let l = unsafe { link.as_ref() };
+        let (prev, next) = (l.prev.get(), l.next.get());
+        unsafe {
+            match prev {
+                Some(prev) => prev.as_ref().next.set(next),
+                None => self.head = next,
+            }
+            match next {
+                Some(next) => next.as_ref().prev.set(prev),
+                None => self.tail = prev,
+            }
+        }
+        l.prev.set(None);
+        l.next.set(None);
+        l.linked.set(false);
+        self.len -= 1;
+    }
+}
+
+impl<T: Linked> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    struct Node {
+        value: u32,
+        link: Link,
+    }
+    crate::impl_linked!(Node, link);
+
+    fn nodes(count: u32) -> Vec<Node> {
+        (0..count).map(|value| Node { value, link: Link::new() }).collect()
+    }
+
+    fn values(list: &List<Node>) -> Vec<u32> {
+        list.iter().map(|node| unsafe { node.as_ref() }.value).collect()
+    }
+
+    #[test]
+    fn push_front_and_back_keep_order() {
+        let items = nodes(3);
+        let mut list = List::new();
+        unsafe {
+            list.push_back(NonNull::from(&items[1]));
+            list.push_back(NonNull::from(&items[2]));
+            list.push_front(NonNull::from(&items[0]));
+        }
+        assert_eq!(values(&list), [0, 1, 2]);
+        assert_eq!(list.len(), 3);
+        assert!(items.iter().all(|node| node.link.is_linked()));
+        assert_eq!(list.front(), Some(NonNull::from(&items[0])));
+    }
+
+    #[test]
+    fn pop_from_both_ends_unlinks() {
+        let items = nodes(3);
+        let mut list = List::new();
+        for node in &items {
+            unsafe { list.push_back(NonNull::from(node)) };
+        }
+
+        assert_eq!(list.pop_front(), Some(NonNull::from(&items[0])));
+        assert_eq!(list.pop_back(), Some(NonNull::from(&items[2])));
+        assert!(!items[0].link.is_linked());
+        assert!(!items[2].link.is_linked());
+        assert_eq!(values(&list), [1]);
+
+        assert_eq!(list.pop_back(), Some(NonNull::from(&items[1])));
+        assert!(list.is_empty());
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.front(), None);
+    }
+
+    #[test]
+    fn remove_from_middle_and_ends() {
+        let items = nodes(5);
+        let mut list = List::new();
+        for node in &items {
+            unsafe { list.push_back(NonNull::from(node)) };
+        }
+
+        unsafe {
+            list.remove(NonNull::from(&items[2]));
+            list.remove(NonNull::from(&items[0]));
+            list.remove(NonNull::from(&items[4]));
+        }
+        assert_eq!(values(&list), [1, 3]);
+        assert_eq!(list.len(), 2);
+
+        // Links are reset, so a removed element can join again.
+        unsafe { list.push_front(NonNull::from(&items[2])) };
+        assert_eq!(values(&list), [2, 1, 3]);
+        assert_eq!(list.pop_back(), Some(NonNull::from(&items[3])));
+        assert_eq!(values(&list), [2, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already linked")]
+    fn double_push_panics() {
+        let items = nodes(1);
+        let mut list = List::new();
+        unsafe {
+            list.push_back(NonNull::from(&items[0]));
+            list.push_back(NonNull::from(&items[0]));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not linked")]
+    fn removing_unlinked_element_panics() {
+        let items = nodes(1);
+        let mut list = List::<Node>::new();
+        unsafe { list.remove(NonNull::from(&items[0])) };
+    }
+}
@@ -0,0 +1,401 @@
+//! Fixed-capacity ring buffers (`ring.rs`).
+//!
+//! - `RingBuffer<T, N>`: plain ring with no internal synchronization; wrap
+//!   it in a lock. `push` refuses when full, `push_overwrite` evicts the
+//!   oldest element (log-style).
+//! - `SpscRing<T, N>`: lock-free ring for exactly one producer and one
+//!   consumer, e.g. an interrupt handler feeding a task.
+//! - `MpscRing<T, N>`: any number of producers, one consumer; a spinlock
+//!   taken with interrupts disabled, so handlers and tasks can share it.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+/// Fixed-capacity FIFO holding up to `N` elements.
+pub struct RingBuffer<T, const N: usize> {
+    slots: [MaybeUninit<T>; N],
+    /// Index of the oldest element.
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        RingBuffer { slots: [const { MaybeUninit::uninit() }; N], head: 0, len: 0 }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Append `value`, handing it back if the ring is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        self.slots[(self.head + self.len) % N].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Append `value`, evicting and returning the oldest element if full.
+    pub fn push_overwrite(&mut self, value: T) -> Option<T> {
+        let evicted = if self.is_full() { self.pop() } else { None };
+        // Cannot fail: there is room now (or N == 0, where nothing is kept).
+        let _ = self.push(value);
+        evicted
+    }
+
+    /// Remove the oldest element.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let value = unsafe { self.slots[self.head].assume_init_read() };
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// The oldest element.
+    pub fn peek(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    /// The `i`-th element, oldest first.
+    pub fn get(&self, i: usize) -> Option<&T> {
+        if i >= self.len {
+            return None;
+        }
+        Some(unsafe { self.slots[(self.head + i) % N].assume_init_ref() })
+    }
+
+    /// Elements, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        (0..self.len).map(move |i| unsafe { self.slots[(self.head + i) % N].assume_init_ref() })
+    }
+
+    /// Drop every element.
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lock-free single-producer/single-consumer ring of `N - 1` elements.
+///
+/// One slot stays empty to tell full from empty. Only one context may call
+/// `push` and only one may call `pop` at a time; each side may be an
+/// interrupt handler. Breaking that rule corrupts the ring.
+pub struct SpscRing<T, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// Next slot to read (owned by the consumer).
+    head: AtomicUsize,
+    /// Next slot to write (owned by the producer).
+    tail: AtomicUsize,
+}
+
+// Safety: each slot is accessed by one side at a time, handed over through
+// the acquire/release on `head` and `tail`.
+unsafe impl<T: Send, const N: usize> Sync for SpscRing<T, N> {}
+
+impl<T, const N: usize> SpscRing<T, N> {
+    pub const fn new() -> Self {
+        assert!(N >= 2, "SpscRing needs at least two slots");
+        SpscRing {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Elements the ring can hold.
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Producer side: append `value`, handing it back if the ring is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % N;
+        if next == self.head.load(Ordering::Acquire) {
+            return Err(value);
+        }
+        unsafe { (*self.slots[tail].get()).write(value) };
+        self.tail.store(next, Ordering::Release);
+        Ok(())
+    }
+
+    /// Consumer side: remove the oldest element.
+    pub fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let value = unsafe { (*self.slots[head].get()).assume_init_read() };
+        self.head.store((head + 1) % N, Ordering::Release);
+        Some(value)
+    }
+
+    /// Elements currently queued (a snapshot; may be stale immediately).
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        (tail + N - head) % N
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, const N: usize> Drop for SpscRing<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T, const N: usize> Default for SpscRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Multi-producer ring: a `RingBuffer` behind a spinlock held with
+/// interrupts disabled, so an interrupt handler cannot deadlock against
+/// the code it interrupted.
+pub struct MpscRing<T, const N: usize> {
+    inner: Mutex<RingBuffer<T, N>>,
+}
+
+impl<T, const N: usize> MpscRing<T, N> {
+    pub const fn new() -> Self {
+        MpscRing { inner: Mutex::new(RingBuffer::new()) }
+    }
+
+    /// Run `f` on the ring with the lock held and interrupts off.
+    pub fn with<R>(&self, f: impl FnOnce(&mut RingBuffer<T, N>) -> R) -> R {
+        interrupts::without_interrupts(|| f(&mut self.inner.lock()))
+    }
+
+    /// Append `value`, handing it back if the ring is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        self.with(|ring| ring.push(value))
+    }
+
+    /// Append `value`, evicting and returning the oldest element if full.
+    pub fn push_overwrite(&self, value: T) -> Option<T> {
+        self.with(|ring| ring.push_overwrite(value))
+    }
+
+    /// Remove the oldest element.
+    pub fn pop(&self) -> Option<T> {
+        self.with(|ring| ring.pop())
+    }
+
+    pub fn len(&self) -> usize {
+        self.with(|ring| ring.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, const N: usize> Default for MpscRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    fn contents<const N: usize>(ring: &RingBuffer<u32, N>) -> Vec<u32> {
+        ring.iter().copied().collect()
+    }
+
+    #[test]
+    fn ring_is_fifo_and_refuses_when_full() {
+        let mut ring = RingBuffer::<u32, 3>::new();
+        assert!(ring.is_empty());
+        assert_eq!(ring.pop(), None);
+
+        for i in 0..3 {
+            assert_eq!(ring.push(i), Ok(()));
+        }
+        assert!(ring.is_full());
+        assert_eq!(ring.push(9), Err(9));
+        assert_eq!(ring.len(), 3);
+
+        assert_eq!(ring.pop(), Some(0));
+        assert_eq!(ring.pop(), Some(1));
+        assert_eq!(ring.pop(), Some(2));
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn ring_wraps_around() {
+        let mut ring = RingBuffer::<u32, 4>::new();
+        for i in 0..3 {
+            ring.push(i).unwrap();
+        }
+        // Ten more pass through a ring of four, so head wraps several times.
+        for i in 3..13 {
+            ring.push(i).unwrap();
+            assert_eq!(ring.pop(), Some(i - 3));
+        }
+        assert_eq!(contents(&ring), [10, 11, 12]);
+        ring.push(13).unwrap();
+        assert!(ring.is_full());
+        assert_eq!(ring.peek(), Some(&10));
+        assert_eq!(ring.get(3), Some(&13));
+        assert_eq!(ring.get(4), None);
+    }
+
+    #[test]
+    fn ring_push_overwrite_evicts_oldest() {
+        let mut ring = RingBuffer::<u32, 3>::new();
+        assert_eq!(ring.push_overwrite(1), None);
+        assert_eq!(ring.push_overwrite(2), None);
+        assert_eq!(ring.push_overwrite(3), None);
+        assert_eq!(ring.push_overwrite(4), Some(1));
+        assert_eq!(ring.push_overwrite(5), Some(2));
+        assert_eq!(contents(&ring), [3, 4, 5]);
+    }
+
+    #[test]
+    fn ring_of_zero_capacity_keeps_nothing() {
+        let mut ring = RingBuffer::<u32, 0>::new();
+        assert!(ring.is_full());
+        assert_eq!(ring.push(1), Err(1));
+        assert_eq!(ring.push_overwrite(2), None);
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn ring_drops_remaining_elements() {
+        let item = Rc::new(());
+        {
+            let mut ring = RingBuffer::<Rc<()>, 4>::new();
+            for _ in 0..6 {
+                ring.push_overwrite(item.clone());
+                ring.pop();
+                ring.push(item.clone()).unwrap();
+            }
+            assert_eq!(Rc::strong_count(&item), 5);
+        }
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn ring_clear_drops_elements() {
+        let item = Rc::new(());
+        let mut ring = RingBuffer::<Rc<()>, 2>::new();
+        ring.push(item.clone()).unwrap();
+        ring.push(item.clone()).unwrap();
+        ring.clear();
+        assert!(ring.is_empty());
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn spsc_holds_one_less_than_its_slots() {
+        let ring = SpscRing::<u32, 4>::new();
+        assert_eq!(ring.capacity(), 3);
+        assert!(ring.is_empty());
+        for i in 0..3 {
+            ring.push(i).unwrap();
+        }
+        assert_eq!(ring.push(3), Err(3));
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.pop(), Some(0));
+        ring.push(3).unwrap();
+        assert_eq!(ring.len(), 3);
+    }
+
+    #[test]
+    fn spsc_wraps_around() {
+        let ring = SpscRing::<u32, 3>::new();
+        for i in 0..20 {
+            ring.push(i).unwrap();
+            assert_eq!(ring.len(), 1);
+            assert_eq!(ring.pop(), Some(i));
+            assert_eq!(ring.pop(), None);
+        }
+    }
+
+    #[test]
+    fn spsc_drops_remaining_elements() {
+        let item = Arc::new(());
+        {
+            let ring = SpscRing::<Arc<()>, 4>::new();
+            for _ in 0..5 {
+                ring.push(item.clone()).unwrap();
+                ring.pop();
+            }
+            ring.push(item.clone()).unwrap();
+            ring.push(item.clone()).unwrap();
+            assert_eq!(Arc::strong_count(&item), 3);
+        }
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn spsc_delivers_in_order_across_threads() {
+        const COUNT: u32 = 10_000;
+        let ring = SpscRing::<u32, 8>::new();
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..COUNT {
+                    while ring.push(i).is_err() {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+
+            let mut expected = 0;
+            while expected < COUNT {
+                match ring.pop() {
+                    Some(value) => {
+                        assert_eq!(value, expected);
+                        expected += 1;
+                    }
+                    None => std::thread::yield_now(),
+                }
+            }
+        });
+        assert!(ring.is_empty());
+    }
+}
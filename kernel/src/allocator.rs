@@ -3,6 +3,10 @@
 //! - Defines the global allocator (`ALLOCATOR`) used by the kernel.
 //! - Provides `init_heap` to map heap pages and initialize the allocator.
 //! - Wraps `spin::Mutex` in `Locked` for safe trait implementations.
+//! - `dump_heap` logs per-size-class usage for leak triage and the fallback
+//!   heap's used/free/largest-allocatable bytes for fragmentation.
+//! - `check_heap` (feature `heap-canary`) verifies canaries and poison.
+//! - `poison_heap` (feature `panic-selftest`) wrecks the heap on purpose.
 //! - Re‑exports submodules (`fixed_size_block`, `linked_list`) for allocator strategies.
 
 use alloc::alloc::{GlobalAlloc, Layout};
@@ -99,6 +103,54 @@ pub fn init_heap(
     Ok(())
 }
 
+/// Log a per-size-class summary of heap usage.
+///
+/// Compare two dumps taken some time apart: a class whose live count only
+/// grows is the first place to look for a leak.
+pub fn dump_heap() {
+    // Snapshot first: logging may itself allocate.
+    let stats = ALLOCATOR.lock().stats();
+
+    info!(
+        "heap: {:>6} {:>8} {:>8} {:>8} {:>6} {:>8}",
+        "class", "allocs", "frees", "live", "free", "trimmed"
+    );
+    for (size, counters, free_blocks) in stats.classes.iter() {
+        if counters.allocs == 0 {
+            continue;
+        }
+        info!(
+            "heap: {:>6} {:>8} {:>8} {:>8} {:>6} {:>8}",
+            size, counters.allocs, counters.frees, counters.live(), free_blocks, counters.trimmed
+        );
+    }
+    info!(
+        "heap: {:>6} {:>8} {:>8} {:>8} ({} bytes live)",
+        "large", stats.large.allocs, stats.large.frees, stats.large.live(), stats.large_bytes
+    );
+    info!(
+        "heap: fallback {} bytes used, {} bytes free of {}, largest allocatable {}",
+        stats.fallback_used, stats.fallback_free, HEAP_SIZE, stats.fallback_largest
+    );
+    info!("heap: {} free-list trim pass(es)", stats.trim_passes);
+}
+
+/// Verify heap canaries and free-block poison; panics on corruption.
+#[cfg(feature = "heap-canary")]
+pub fn check_heap() {
+    let checked = fixed_size_block::check_heap(&ALLOCATOR);
+    debug!("heap: canary check passed ({} blocks)", checked);
+}
+
+/// Fill the heap with garbage and leave it locked forever, so any later
+/// allocation spins (feature `panic-selftest`). Panic output must survive this.
+#[cfg(feature = "panic-selftest")]
+pub fn poison_heap() {
+    let guard = ALLOCATOR.lock();
+    unsafe { core::ptr::write_bytes(HEAP_START as *mut u8, 0xDE, HEAP_SIZE) };
+    core::mem::forget(guard);
+}
+
 /// Wrapper around `spin::Mutex` to permit trait implementations.
 /// 
 /// Provides a simple lock/unlock interface for allocator types.
@@ -118,6 +170,11 @@ impl<A> Locked<A> {
     pub fn lock(&self) -> spin::MutexGuard<A> {
         self.inner.lock()
     }
+
+    /// Acquire the lock if it is free.
+    pub fn try_lock(&self) -> Option<spin::MutexGuard<A>> {
+        self.inner.try_lock()
+    }
 }
 
 
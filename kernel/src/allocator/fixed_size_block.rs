@@ -7,10 +7,25 @@
 //! - Fallback: delegate to `linked_list_allocator::LockedHeap`.
 //! - Global usage: wrapped by `Locked<FixedSizeBlockAllocator>` to implement `GlobalAlloc`.
 //!
+//! - Free lists start empty: blocks are carved from the fallback heap on first use
+//!   and recycled through their size-class list on `dealloc`.
+//! - Feature `heap-quarantine`: freed blocks are poisoned and held in a FIFO
+//!   quarantine before reuse; the poison is verified on eviction and again on
+//!   allocation, catching writes through dangling pointers.
+//! - Feature `heap-canary`: every allocation is wrapped in a header and
+//!   trailer canary (checked on `dealloc`), freed blocks are poisoned
+//!   (checked on reuse), and `check_heap` re-verifies all tracked live
+//!   blocks and free lists; the health check calls it periodically.
+//!
+//! - Per-size-class allocation/free counters feed `stats()` (see `allocator::dump_heap`),
+//!   along with the fallback's used/free bytes and largest allocatable block.
+//! - Trimming: when a free list holds more than `TRIM_HIGH_WATER` bytes, surplus
+//!   blocks that form address-contiguous runs are returned to the fallback heap
+//!   until the list is back under `TRIM_LOW_WATER`. A fallback allocation that
+//!   fails trims every class and retries once.
+//!
 //! Safety notes:
 //! - `init(heap_start, heap_size)` must be called once with a valid, unused heap region.
-//! - `add_region` carves the heap into block-size-aligned free lists; the caller must
-//!   ensure the region is exclusively owned by the allocator.
 
 use super::Locked;
 use alloc::alloc::{GlobalAlloc, Layout};
@@ -18,7 +33,7 @@ use core::{
     mem,
     ptr::{self, NonNull},
 };
-use linked_list_allocator::LockedHeap;
+use linked_list_allocator::{Heap, LockedHeap};
 
 /// Singly linked list node representing a free block of a given size class.
 #[repr(C)]
@@ -38,6 +53,19 @@ pub struct AllocError;
 /// - Covers common small allocations (`Vec`, `Box`, `String`, small structs).
 const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048, 4096];
 
+/// Free-list bytes per class above which `dealloc` trims the list.
+const TRIM_HIGH_WATER: usize = 16 * 1024;
+
+/// Free-list bytes per class a trim tries to get down to.
+const TRIM_LOW_WATER: usize = TRIM_HIGH_WATER / 2;
+
+/// Blocks examined per trim pass (sorted on the stack).
+const TRIM_BATCH: usize = 64;
+
+/// First class eligible for trimming: the fallback pads smaller blocks to
+/// 16 bytes, so 8-byte blocks never sit back to back.
+const TRIM_FIRST_CLASS: usize = 1;
+
 /// Choose the free-list index for the given layout.
 /// Returns `Some(index)` if a suitable size class exists, otherwise `None`.
 fn list_index(layout: &Layout) -> Option<usize> {
@@ -45,12 +73,91 @@ fn list_index(layout: &Layout) -> Option<usize> {
     BLOCK_SIZES.iter().position(|&s| s >= required_block_size)
 }
 
+/// Allocation and free counts for one size class (or the fallback path).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassCounters {
+    pub allocs: u64,
+    pub frees: u64,
+    /// Free blocks returned to the fallback heap by trimming.
+    pub trimmed: u64,
+}
+
+impl ClassCounters {
+    const fn new() -> Self {
+        ClassCounters { allocs: 0, frees: 0, trimmed: 0 }
+    }
+
+    /// Allocations not yet freed.
+    pub fn live(&self) -> u64 {
+        self.allocs.saturating_sub(self.frees)
+    }
+}
+
+/// Snapshot of allocator usage returned by `FixedSizeBlockAllocator::stats`.
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    /// Block size, counters and free-list length for each size class.
+    pub classes: [(usize, ClassCounters, usize); BLOCK_SIZES.len()],
+    /// Requests too large for any size class.
+    pub large: ClassCounters,
+    /// Bytes currently held by large allocations.
+    pub large_bytes: usize,
+    /// Fallback heap usage (includes blocks carved for size classes).
+    pub fallback_used: usize,
+    pub fallback_free: usize,
+    /// Largest single allocation the fallback can serve; far below
+    /// `fallback_free` means the free space is fragmented.
+    pub fallback_largest: usize,
+    /// Trim passes run since boot.
+    pub trim_passes: u64,
+}
+
+/// Granularity of `largest_allocatable`: the fallback rounds every request
+/// up to a multiple of this anyway.
+const PROBE_STEP: usize = mem::size_of::<usize>();
+
+/// Size of the largest block `heap` can hand out right now.
+///
+/// The fallback exposes no list of its holes, so this binary-searches with
+/// trial allocations. Each one is freed immediately and merges straight back
+/// into the hole it came from.
+fn largest_allocatable(heap: &mut Heap) -> usize {
+    // In `PROBE_STEP` units: `fits` is known to succeed, `too_big` to fail.
+    let (mut fits, mut too_big) = (0, heap.free() / PROBE_STEP + 1);
+    while too_big - fits > 1 {
+        let mid = (fits + too_big) / 2;
+        let layout = Layout::from_size_align(mid * PROBE_STEP, PROBE_STEP).unwrap();
+        match heap.allocate_first_fit(layout) {
+            Ok(ptr) => {
+                unsafe { heap.deallocate(ptr, layout) };
+                fits = mid;
+            }
+            Err(_) => too_big = mid,
+        }
+    }
+    fits * PROBE_STEP
+}
+
 /// Fixed-size block allocator with per-size free lists and fallback allocator.
 pub struct FixedSizeBlockAllocator {
     /// Free-list heads for each size class in `BLOCK_SIZES`.
     list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
+    /// Length of each free list.
+    free_counts: [usize; BLOCK_SIZES.len()],
+    trim_passes: u64,
+    /// Counters for each size class in `BLOCK_SIZES`.
+    class_counters: [ClassCounters; BLOCK_SIZES.len()],
+    /// Counters and live bytes for requests served directly by the fallback.
+    large_counters: ClassCounters,
+    large_bytes: usize,
     /// Fallback allocator for requests that don't fit a size class.
     fallback_allocator: LockedHeap,
+    /// Recently freed blocks held back from reuse.
+    #[cfg(feature = "heap-quarantine")]
+    quarantine: Quarantine,
+    /// Live canary-wrapped allocations, for `check_heap`.
+    #[cfg(feature = "heap-canary")]
+    live: canary::LiveTable,
 }
 
 impl FixedSizeBlockAllocator {
@@ -59,15 +166,26 @@ impl FixedSizeBlockAllocator {
         const NONE: Option<&'static mut ListNode> = None;
         FixedSizeBlockAllocator {
             list_heads: [NONE; BLOCK_SIZES.len()],
+            free_counts: [0; BLOCK_SIZES.len()],
+            trim_passes: 0,
+            class_counters: [ClassCounters::new(); BLOCK_SIZES.len()],
+            large_counters: ClassCounters::new(),
+            large_bytes: 0,
             fallback_allocator: LockedHeap::empty(),
+            #[cfg(feature = "heap-quarantine")]
+            quarantine: Quarantine::new(),
+            #[cfg(feature = "heap-canary")]
+            live: canary::LiveTable::new(),
         }
     }
 
     /// Initialize the allocator with the given heap bounds.
     ///
     /// - Aligns the heap start to satisfy stricter layout requirements.
-    /// - Initializes the fallback allocator with the aligned region.
-    /// - Carves the region into per-size-class free lists by calling `add_region`.
+    /// - Hands the whole aligned region to the fallback allocator.
+    ///
+    /// The per-size-class free lists start empty and are populated by `dealloc`;
+    /// seeding them from the same region would hand out overlapping blocks.
     ///
     /// Safety:
     /// - `heap_start..heap_start+heap_size` must be a valid, unused, exclusively owned region.
@@ -79,11 +197,6 @@ impl FixedSizeBlockAllocator {
 
         // Initialize fallback allocator with aligned region.
         self.fallback_allocator.lock().init(aligned_start, adjusted_size);
-
-        // Populate per-size-class free lists from the same aligned region.
-        for &block_size in BLOCK_SIZES {
-            self.add_region(aligned_start, adjusted_size, block_size);
-        }
     }
 
     /// Allocate using the fallback allocator.
@@ -98,40 +211,131 @@ impl FixedSizeBlockAllocator {
         }
     }
 
-    /// Add a region of memory to a size-class free list.
-    ///
-    /// - Walks the region in `block_size` chunks.
-    /// - Each chunk becomes a `ListNode` in the free list for that size class.
+    /// Snapshot usage counters, free-list lengths and fallback heap usage.
+    pub fn stats(&self) -> HeapStats {
+        let mut classes = [(0, ClassCounters::new(), 0); BLOCK_SIZES.len()];
+        for (index, class) in classes.iter_mut().enumerate() {
+            *class = (BLOCK_SIZES[index], self.class_counters[index], self.free_counts[index]);
+        }
+
+        let mut fallback = self.fallback_allocator.lock();
+        HeapStats {
+            classes,
+            large: self.large_counters,
+            large_bytes: self.large_bytes,
+            fallback_used: fallback.used(),
+            fallback_free: fallback.free(),
+            fallback_largest: largest_allocatable(&mut fallback),
+            trim_passes: self.trim_passes,
+        }
+    }
+
+    /// Push a block onto its class's free list.
+    unsafe fn push_free(&mut self, ptr: *mut u8, index: usize) {
+        // Ensure we can store a ListNode in this block.
+        assert!(mem::size_of::<ListNode>() <= BLOCK_SIZES[index]);
+        assert!(mem::align_of::<ListNode>() <= BLOCK_SIZES[index]);
+
+        let new_node = ListNode {
+            next: self.list_heads[index].take(),
+        };
+        let new_node_ptr = ptr as *mut ListNode;
+        new_node_ptr.write(new_node);
+        self.list_heads[index] = Some(&mut *new_node_ptr);
+        self.free_counts[index] += 1;
+    }
+
+    /// Pop a block from a class's free list.
+    fn pop_free(&mut self, index: usize) -> Option<*mut u8> {
+        let node = self.list_heads[index].take()?;
+        self.list_heads[index] = node.next.take();
+        self.free_counts[index] -= 1;
+        Some(node as *mut ListNode as *mut u8)
+    }
+
+    /// Return contiguous runs of surplus free blocks in class `index` to the
+    /// fallback heap, aiming to leave at most `keep_bytes` on the list.
     ///
-    /// Safety:
-    /// - Caller guarantees that `heap_start..heap_start+heap_size` is valid and writable.
-    /// - Region must not overlap with other allocator uses.
-    unsafe fn add_region(&mut self, heap_start: usize, heap_size: usize, block_size: usize) {
-        assert!(block_size.is_power_of_two());
+    /// Examines up to `TRIM_BATCH` blocks; blocks not part of a run of two
+    /// or more go back on the list. Returns the number of blocks released.
+    fn trim_class(&mut self, index: usize, keep_bytes: usize) -> usize {
+        let size = BLOCK_SIZES[index];
+        let surplus = self.free_counts[index].saturating_sub(keep_bytes / size);
+        if index < TRIM_FIRST_CLASS || surplus == 0 {
+            return 0;
+        }
+        self.trim_passes += 1;
 
-        let aligned_start = align_up(heap_start, block_size);
-        let end = heap_start.saturating_add(heap_size);
+        let mut batch = [0usize; TRIM_BATCH];
+        let mut taken = 0;
+        while taken < surplus.min(TRIM_BATCH) {
+            match self.pop_free(index) {
+                Some(block) => batch[taken] = block as usize,
+                None => break,
+            }
+            taken += 1;
+        }
+        let batch = &mut batch[..taken];
+        batch.sort_unstable();
 
-        let mut current = aligned_start;
+        let mut released = 0;
+        let mut start = 0;
+        while start < batch.len() {
+            let mut end = start + 1;
+            while end < batch.len() && batch[end] == batch[end - 1] + size {
+                end += 1;
+            }
+            let run = end - start;
+            if run >= 2 {
+                // The fallback merges the run back into one hole.
+                let layout = Layout::from_size_align(run * size, size).unwrap();
+                unsafe { self.fallback_allocator.dealloc(batch[start] as *mut u8, layout) };
+                released += run;
+            } else {
+                unsafe { self.push_free(batch[start] as *mut u8, index) };
+            }
+            start = end;
+        }
 
-        while current.saturating_add(block_size) <= end {
-            let node = current as *mut ListNode;
+        self.class_counters[index].trimmed += released as u64;
+        released
+    }
 
-            let index = Self::list_index_for(block_size);
-            let prev_head = self.list_heads[index].take();
-            (*node).next = prev_head;
-            self.list_heads[index] = Some(&mut *node);
+    /// Trim every class down to `keep_bytes` of free blocks.
+    ///
+    /// Returns the number of blocks released to the fallback heap.
+    pub fn trim(&mut self, keep_bytes: usize) -> usize {
+        (0..BLOCK_SIZES.len()).map(|index| self.trim_class(index, keep_bytes)).sum()
+    }
 
-            current = current.saturating_add(block_size);
+    /// Shrink a large allocation in place by freeing its tail to the fallback.
+    ///
+    /// The fallback pads every request to at least 16 bytes and a multiple of
+    /// 8, and a later `dealloc` with the new size frees exactly the padded new
+    /// size, so the tail is the difference of the two padded sizes. Returns
+    /// `false` if that tail is too small to become a fallback hole.
+    unsafe fn shrink_large(&mut self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        let footprint = |size: usize| align_up(size.max(2 * mem::size_of::<usize>()), 8);
+        let (old, new) = (footprint(layout.size()), footprint(new_size));
+        let tail = old - new;
+        if tail != 0 && tail < 2 * mem::size_of::<usize>() {
+            return false;
+        }
+        if tail != 0 {
+            let tail_layout = Layout::from_size_align_unchecked(tail, 8);
+            self.fallback_allocator.dealloc(ptr.add(new), tail_layout);
         }
+        self.large_bytes -= layout.size() - new_size;
+        true
     }
 
-    /// Return the index for the exact `block_size`.
-    fn list_index_for(block_size: usize) -> usize {
-        BLOCK_SIZES
-            .iter()
-            .position(|&s| s == block_size)
-            .expect("Invalid block size")
+    /// Allocate from the fallback, trimming all free lists and retrying once on failure.
+    fn fallback_alloc_or_trim(&mut self, layout: Layout) -> *mut u8 {
+        let ptr = self.fallback_alloc(layout);
+        if !ptr.is_null() || self.trim(0) == 0 {
+            return ptr;
+        }
+        self.fallback_alloc(layout)
     }
 }
 
@@ -142,50 +346,133 @@ pub fn align_up(addr: usize, align: usize) -> usize {
 }
 
 unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
-    /// Allocate memory for `layout`.
+    /// Allocate memory for `layout` (wrapped in canaries with `heap-canary`).
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        #[cfg(feature = "heap-canary")]
+        return canary::alloc(self, layout);
+        #[cfg(not(feature = "heap-canary"))]
+        self.alloc_block(layout)
+    }
+
+    /// Deallocate memory at `ptr` for `layout`.
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        #[cfg(feature = "heap-canary")]
+        return canary::dealloc(self, ptr, layout);
+        #[cfg(not(feature = "heap-canary"))]
+        self.dealloc_block(ptr, layout)
+    }
+
+    /// Allocate zeroed memory for `layout`.
+    ///
+    /// Recycled blocks hold stale (or poisoned) data, so only the requested
+    /// bytes are cleared, not the whole size-class block.
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.alloc(layout);
+        if !ptr.is_null() {
+            ptr::write_bytes(ptr, 0, layout.size());
+        }
+        ptr
+    }
+
+    /// Resize the allocation at `ptr` to `new_size`.
+    ///
+    /// - Old and new sizes in the same size class: the block already fits, return it.
+    /// - Shrinking a large allocation: hand the tail back to the fallback heap.
+    /// - Otherwise allocate, copy and free.
+    ///
+    /// With `heap-canary` every resize moves, so the trailer canary is rewritten.
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+
+        #[cfg(not(feature = "heap-canary"))]
+        match (list_index(&layout), list_index(&new_layout)) {
+            (Some(old), Some(new)) if old == new => return ptr,
+            (None, None) if new_size <= layout.size() => {
+                let mut allocator = self.lock();
+                if allocator.shrink_large(ptr, layout, new_size) {
+                    return ptr;
+                }
+            }
+            _ => {}
+        }
+
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+impl Locked<FixedSizeBlockAllocator> {
+    /// Allocate a block for `layout`.
     ///
     /// Strategy:
     /// - If `layout` fits a size class, pop from the corresponding free list.
     /// - Otherwise, or if the list is empty, delegate to the fallback allocator.
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    unsafe fn alloc_block(&self, layout: Layout) -> *mut u8 {
         let mut allocator = self.lock();
         match list_index(&layout) {
-            Some(index) => match allocator.list_heads[index].take() {
-                Some(node) => {
-                    allocator.list_heads[index] = node.next.take();
-                    node as *mut ListNode as *mut u8
+            Some(index) => {
+                let block = match allocator.pop_free(index) {
+                    Some(block) => {
+                        #[cfg(any(feature = "heap-quarantine", feature = "heap-canary"))]
+                        verify_poison(block, index, "reuse");
+                        block
+                    }
+                    None => {
+                        let block_size = BLOCK_SIZES[index];
+                        let layout = Layout::from_size_align(block_size, block_size).unwrap();
+                        allocator.fallback_alloc_or_trim(layout)
+                    }
+                };
+                if !block.is_null() {
+                    allocator.class_counters[index].allocs += 1;
                 }
-                None => {
-                    let block_size = BLOCK_SIZES[index];
-                    let layout = Layout::from_size_align(block_size, block_size).unwrap();
-                    FixedSizeBlockAllocator::fallback_alloc(&allocator, layout)
+                block
+            }
+            None => {
+                let ptr = allocator.fallback_alloc_or_trim(layout);
+                if !ptr.is_null() {
+                    allocator.large_counters.allocs += 1;
+                    allocator.large_bytes += layout.size();
                 }
-            },
-            None => FixedSizeBlockAllocator::fallback_alloc(&allocator, layout),
+                ptr
+            }
         }
     }
 
-    /// Deallocate memory at `ptr` for `layout`.
+    /// Return a block from `alloc_block`.
     ///
     /// Strategy:
-    /// - If `layout` fits a size class, push the block back onto that free list.
+    /// - If `layout` fits a size class, push the block back onto that free list,
+    ///   trimming the list if it has grown past `TRIM_HIGH_WATER`.
     /// - Otherwise, delegate to the fallback allocator.
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    unsafe fn dealloc_block(&self, ptr: *mut u8, layout: Layout) {
         let mut allocator = self.lock();
         match list_index(&layout) {
             Some(index) => {
-                let new_node = ListNode {
-                    next: allocator.list_heads[index].take(),
+                allocator.class_counters[index].frees += 1;
+
+                // Park the block in quarantine; recycle whichever block it evicts.
+                #[cfg(feature = "heap-quarantine")]
+                let (ptr, index) = match allocator.quarantine.push(ptr, index) {
+                    Some(evicted) => evicted,
+                    None => return,
                 };
-                // Ensure we can store a ListNode in this block.
-                assert!(mem::size_of::<ListNode>() <= BLOCK_SIZES[index]);
-                assert!(mem::align_of::<ListNode>() <= BLOCK_SIZES[index]);
 
-                let new_node_ptr = ptr as *mut ListNode;
-                new_node_ptr.write(new_node);
-                allocator.list_heads[index] = Some(&mut *new_node_ptr);
+                #[cfg(all(feature = "heap-canary", not(feature = "heap-quarantine")))]
+                ptr::write_bytes(ptr, POISON_BYTE, BLOCK_SIZES[index]);
+
+                allocator.push_free(ptr, index);
+                if allocator.free_counts[index] * BLOCK_SIZES[index] > TRIM_HIGH_WATER {
+                    allocator.trim_class(index, TRIM_LOW_WATER);
+                }
             }
             None => {
+                allocator.large_counters.frees += 1;
+                allocator.large_bytes -= layout.size();
                 let ptr = NonNull::new(ptr).unwrap();
                 allocator.fallback_allocator.dealloc(ptr.as_ptr(), layout);
             }
@@ -193,3 +480,223 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
     }
 }
 
+/// Byte pattern written over freed blocks while `heap-quarantine` or
+/// `heap-canary` is enabled.
+#[cfg(any(feature = "heap-quarantine", feature = "heap-canary"))]
+pub const POISON_BYTE: u8 = 0xDF;
+
+/// Number of freed blocks held back before they become reusable.
+#[cfg(feature = "heap-quarantine")]
+const QUARANTINE_SLOTS: usize = 64;
+
+/// FIFO of freed blocks (address, size-class index) awaiting reuse.
+#[cfg(feature = "heap-quarantine")]
+struct Quarantine {
+    slots: [Option<(usize, usize)>; QUARANTINE_SLOTS],
+    next: usize,
+}
+
+#[cfg(feature = "heap-quarantine")]
+impl Quarantine {
+    const fn new() -> Self {
+        Quarantine {
+            slots: [None; QUARANTINE_SLOTS],
+            next: 0,
+        }
+    }
+
+    /// Poison `ptr` and park it, returning the oldest block once the ring is full.
+    ///
+    /// The evicted block's poison is verified before it is handed back.
+    unsafe fn push(&mut self, ptr: *mut u8, index: usize) -> Option<(*mut u8, usize)> {
+        ptr::write_bytes(ptr, POISON_BYTE, BLOCK_SIZES[index]);
+
+        let evicted = self.slots[self.next].replace((ptr as usize, index));
+        self.next = (self.next + 1) % QUARANTINE_SLOTS;
+
+        evicted.map(|(addr, index)| {
+            let block = addr as *mut u8;
+            verify_poison_from(block, index, 0, "quarantine");
+            (block, index)
+        })
+    }
+}
+
+/// Check that a recycled free-list block still carries its poison.
+///
+/// The first bytes hold the `ListNode` link and are skipped.
+#[cfg(any(feature = "heap-quarantine", feature = "heap-canary"))]
+unsafe fn verify_poison(block: *mut u8, index: usize, stage: &str) {
+    verify_poison_from(block, index, mem::size_of::<ListNode>(), stage);
+}
+
+/// Panic with the block address if any byte from `skip` onwards was overwritten.
+#[cfg(any(feature = "heap-quarantine", feature = "heap-canary"))]
+unsafe fn verify_poison_from(block: *mut u8, index: usize, skip: usize, stage: &str) {
+    let size = BLOCK_SIZES[index];
+    for offset in skip..size {
+        if *block.add(offset) != POISON_BYTE {
+            log::error!(
+                "heap: use-after-free detected at {:#x}+{} ({}-byte block, during {})",
+                block as usize,
+                offset,
+                size,
+                stage
+            );
+            panic!("heap use-after-free at {:#x}", block as usize + offset);
+        }
+    }
+}
+
+/// Offset of the first byte from `skip` onwards that no longer holds the poison.
+#[cfg(feature = "heap-canary")]
+unsafe fn first_unpoisoned(block: *const u8, index: usize, skip: usize) -> Option<usize> {
+    (skip..BLOCK_SIZES[index]).find(|&offset| *block.add(offset) != POISON_BYTE)
+}
+
+/// Canary wrapping for `heap-canary`.
+///
+/// Layout of a wrapped allocation (`user` is the pointer handed out):
+///
+/// ```text
+/// block .. user-16: padding to the caller's alignment
+/// user-16:          requested size (u64)
+/// user-8:           CANARY_HEAD
+/// user..user+size:  caller data
+/// user+size:        CANARY_TAIL (unaligned u64)
+/// ```
+#[cfg(feature = "heap-canary")]
+mod canary {
+    use super::{first_unpoisoned, FixedSizeBlockAllocator, Locked, ListNode, BLOCK_SIZES};
+    use alloc::alloc::Layout;
+    use core::{mem, ptr};
+
+    const CANARY_HEAD: u64 = 0x5AFE_C0DE_4845_4144;
+    const CANARY_TAIL: u64 = 0x5AFE_C0DE_5441_494C;
+
+    /// Live allocations tracked for `check_heap`; more are checked only on free.
+    const LIVE_SLOTS: usize = 256;
+
+    /// Fixed table of live user pointers (0 = empty slot).
+    pub struct LiveTable {
+        slots: [usize; LIVE_SLOTS],
+        untracked: usize,
+    }
+
+    impl LiveTable {
+        pub const fn new() -> Self {
+            LiveTable { slots: [0; LIVE_SLOTS], untracked: 0 }
+        }
+
+        fn insert(&mut self, user: usize) {
+            match self.slots.iter_mut().find(|s| **s == 0) {
+                Some(slot) => *slot = user,
+                None => self.untracked += 1,
+            }
+        }
+
+        fn remove(&mut self, user: usize) {
+            match self.slots.iter_mut().find(|s| **s == user) {
+                Some(slot) => *slot = 0,
+                None => self.untracked = self.untracked.saturating_sub(1),
+            }
+        }
+    }
+
+    /// Bytes before the user pointer: room for size and head canary, kept
+    /// a multiple of the alignment.
+    fn header(layout: Layout) -> usize {
+        layout.align().max(16)
+    }
+
+    fn outer(layout: Layout) -> Option<Layout> {
+        let size = header(layout).checked_add(layout.size())?.checked_add(8)?;
+        Layout::from_size_align(size, layout.align()).ok()
+    }
+
+    /// What is wrong with the canaries around `user`, if anything.
+    unsafe fn damage(user: *mut u8) -> Option<&'static str> {
+        let size = (user.sub(16) as *const u64).read() as usize;
+        if (user.sub(8) as *const u64).read() != CANARY_HEAD {
+            return Some("header canary overwritten (underflow)");
+        }
+        if (user.add(size) as *const u64).read_unaligned() != CANARY_TAIL {
+            return Some("trailer canary overwritten (overflow)");
+        }
+        None
+    }
+
+    fn report(user: usize, what: &str, stage: &str) -> ! {
+        log::error!("heap: corruption at {:#x}: {} (during {})", user, what, stage);
+        panic!("heap corruption at {:#x}: {}", user, what);
+    }
+
+    pub unsafe fn alloc(heap: &Locked<FixedSizeBlockAllocator>, layout: Layout) -> *mut u8 {
+        let Some(outer) = outer(layout) else {
+            return ptr::null_mut();
+        };
+        let block = heap.alloc_block(outer);
+        if block.is_null() {
+            return block;
+        }
+
+        let user = block.add(header(layout));
+        (user.sub(16) as *mut u64).write(layout.size() as u64);
+        (user.sub(8) as *mut u64).write(CANARY_HEAD);
+        (user.add(layout.size()) as *mut u64).write_unaligned(CANARY_TAIL);
+        heap.lock().live.insert(user as usize);
+        user
+    }
+
+    pub unsafe fn dealloc(heap: &Locked<FixedSizeBlockAllocator>, user: *mut u8, layout: Layout) {
+        if (user.sub(16) as *const u64).read() as usize != layout.size() {
+            report(user as usize, "size header does not match dealloc layout", "free");
+        }
+        if let Some(what) = damage(user) {
+            report(user as usize, what, "free");
+        }
+        heap.lock().live.remove(user as usize);
+        heap.dealloc_block(user.sub(header(layout)), outer(layout).unwrap());
+    }
+
+    /// Verify every tracked live allocation and every free-list block.
+    ///
+    /// The scan runs under the allocator lock; the report is made after
+    /// releasing it, since logging may allocate.
+    pub fn check(heap: &Locked<FixedSizeBlockAllocator>) -> usize {
+        let mut checked = 0;
+        let mut bad: Option<(usize, &'static str)> = None;
+        {
+            let allocator = heap.lock();
+            for &user in allocator.live.slots.iter().filter(|&&u| u != 0) {
+                checked += 1;
+                if let Some(what) = unsafe { damage(user as *mut u8) } {
+                    bad = Some((user, what));
+                    break;
+                }
+            }
+            for index in 0..BLOCK_SIZES.len() {
+                let mut node = allocator.list_heads[index].as_deref();
+                while let (Some(n), None) = (node, bad) {
+                    checked += 1;
+                    let block = n as *const ListNode as *const u8;
+                    if let Some(offset) = unsafe { first_unpoisoned(block, index, mem::size_of::<ListNode>()) } {
+                        bad = Some((block as usize + offset, "freed block written (use-after-free)"));
+                    }
+                    node = n.next.as_deref();
+                }
+            }
+        }
+        if let Some((addr, what)) = bad {
+            report(addr, what, "check_heap");
+        }
+        checked
+    }
+}
+
+/// Verify heap canaries and free-block poison (`heap-canary`), panicking on
+/// corruption. Returns the number of blocks checked.
+#[cfg(feature = "heap-canary")]
+pub fn check_heap(heap: &Locked<FixedSizeBlockAllocator>) -> usize {
+    canary::check(heap)
+}
@@ -0,0 +1,114 @@
+//! Security audit log (`audit.rs`).
+//!
+//! - Security-relevant events (capability denials, permission failures,
+//!   failed execs) are recorded as `AuditRecord`s in a fixed-size ring,
+//!   separate from the general kernel log.
+//! - Recording is rate limited per tick window; suppressed records are
+//!   counted, not silently lost.
+//! - `records` snapshots the ring (oldest first); `log_audit` prints it.
+//!
+//! The ring is a `collections::RingBuffer` so recording never allocates,
+//! even from paths that run with the heap under pressure.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use log::info;
+use spin::Mutex;
+
+use crate::collections::RingBuffer;
+use crate::process::{self, Capabilities, Pid};
+use crate::time;
+
+/// Records kept; older ones are overwritten.
+const AUDIT_CAPACITY: usize = 128;
+
+/// Length of a rate-limit window in ticks.
+const RATE_WINDOW: u64 = 1000;
+
+/// Records accepted per window.
+const RATE_LIMIT: u32 = 32;
+
+/// What happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEvent {
+    /// A capability check failed.
+    CapabilityDenied(Capabilities),
+    /// An operation was refused for another reason.
+    PermissionDenied,
+    /// An exec attempt failed.
+    ExecFailed,
+}
+
+/// One audit entry.
+#[derive(Debug, Clone, Copy)]
+pub struct AuditRecord {
+    /// Tick count when recorded.
+    pub tick: u64,
+    pub pid: Pid,
+    pub event: AuditEvent,
+    /// Operation or object involved (e.g. a sysctl name).
+    pub detail: &'static str,
+}
+
+struct AuditLog {
+    ring: RingBuffer<AuditRecord, AUDIT_CAPACITY>,
+    window_start: u64,
+    window_count: u32,
+    /// Records suppressed by the rate limit since boot.
+    suppressed: u64,
+}
+
+static AUDIT: Mutex<AuditLog> = Mutex::new(AuditLog {
+    ring: RingBuffer::new(),
+    window_start: 0,
+    window_count: 0,
+    suppressed: 0,
+});
+
+/// Record `event` for the running process.
+pub fn record(event: AuditEvent, detail: &'static str) {
+    let tick = time::get_ticks();
+    let pid = process::current();
+    let mut log = AUDIT.lock();
+
+    if tick >= log.window_start + RATE_WINDOW {
+        log.window_start = tick;
+        log.window_count = 0;
+    }
+    if log.window_count >= RATE_LIMIT {
+        log.suppressed += 1;
+        return;
+    }
+    log.window_count += 1;
+
+    log.ring.push_overwrite(AuditRecord { tick, pid, event, detail });
+}
+
+/// Snapshot of the ring, oldest record first.
+pub fn records() -> Vec<AuditRecord> {
+    AUDIT.lock().ring.iter().copied().collect()
+}
+
+/// Records dropped by rate limiting since boot.
+pub fn suppressed() -> u64 {
+    AUDIT.lock().suppressed
+}
+
+/// Log the audit ring.
+pub fn log_audit() {
+    for r in records() {
+        match r.event {
+            AuditEvent::CapabilityDenied(cap) => {
+                info!("audit: tick={} pid={} denied {} ({})", r.tick, r.pid, cap.name(), r.detail)
+            }
+            AuditEvent::PermissionDenied => {
+                info!("audit: tick={} pid={} permission denied ({})", r.tick, r.pid, r.detail)
+            }
+            AuditEvent::ExecFailed => {
+                info!("audit: tick={} pid={} exec failed ({})", r.tick, r.pid, r.detail)
+            }
+        }
+    }
+    info!("audit: {} record(s) suppressed by rate limit", suppressed());
+}
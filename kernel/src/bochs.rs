@@ -0,0 +1,224 @@
+//! Bochs/QEMU standard VGA driver (`bochs.rs`).
+//!
+//! - Binds PCI device 1234:1111 (QEMU `-vga std`, Bochs VBE extensions).
+//! - Programs the DISPI registers through index/data ports 0x1CE/0x1CF.
+//! - `set_mode` switches resolution at runtime, maps the linear framebuffer
+//!   (BAR0) and rebinds the console onto it.
+//!
+//! A mode the adapter rejects is rolled back to the previous one before
+//! `set_mode` returns, so the console never draws with the wrong geometry.
+//!
+//! Only 32-bpp modes are offered, matching the console writer. The whole of
+//! VRAM is mapped once, on the first mode switch, and reused afterwards.
+
+use spin::Mutex;
+use log::info;
+use x86_64::{structures::paging::PageTableFlags, PhysAddr, VirtAddr};
+
+use crate::console::{self, ConsoleError};
+use crate::driver::{self, Device, DeviceMatch, Driver, ProbeError};
+use crate::events::{self, Event};
+use crate::framebuffer::FbInfo;
+use crate::ioport::{self, IoPortRange};
+use crate::{memory, pci};
+
+/// QEMU/Bochs standard VGA PCI IDs.
+const VENDOR_ID: u16 = 0x1234;
+const DEVICE_ID: u16 = 0x1111;
+
+/// DISPI index port (the data port follows it).
+const DISPI_PORTS: u16 = 0x01CE;
+
+// DISPI register indices.
+const REG_ID: u16 = 0x0;
+const REG_XRES: u16 = 0x1;
+const REG_YRES: u16 = 0x2;
+const REG_BPP: u16 = 0x3;
+const REG_ENABLE: u16 = 0x4;
+const REG_VIRT_WIDTH: u16 = 0x6;
+const REG_X_OFFSET: u16 = 0x8;
+const REG_Y_OFFSET: u16 = 0x9;
+const REG_VIDEO_MEMORY_64K: u16 = 0xA;
+
+/// Registers that make up a mode, as saved and restored by `set_mode`
+/// (`REG_ENABLE` first).
+const MODE_REGS: [u16; 7] =
+    [REG_ENABLE, REG_XRES, REG_YRES, REG_BPP, REG_VIRT_WIDTH, REG_X_OFFSET, REG_Y_OFFSET];
+
+// REG_ENABLE bits.
+const ENABLED: u16 = 0x01;
+const LFB_ENABLED: u16 = 0x40;
+
+/// First interface revision supporting 32 bpp and a linear framebuffer.
+const DISPI_ID_MIN: u16 = 0xB0C2;
+
+/// VRAM assumed when the device does not report it.
+const DEFAULT_VRAM: u64 = 16 * 1024 * 1024;
+
+/// Bound adapter state.
+struct Bochs {
+    ports: IoPortRange,
+    /// Physical address of the linear framebuffer (BAR0).
+    fb_phys: u64,
+    vram_bytes: u64,
+    /// VRAM mapping, created on the first `set_mode`.
+    fb_virt: Option<VirtAddr>,
+}
+
+impl Bochs {
+    fn read(&self, reg: u16) -> u16 {
+        self.ports.write::<u16>(0, reg);
+        self.ports.read::<u16>(1)
+    }
+
+    fn write(&self, reg: u16, value: u16) {
+        self.ports.write::<u16>(0, reg);
+        self.ports.write::<u16>(1, value);
+    }
+
+    /// Snapshot the mode registers, so a rejected mode can be undone.
+    fn save_mode(&self) -> [u16; MODE_REGS.len()] {
+        MODE_REGS.map(|reg| self.read(reg))
+    }
+
+    /// Reprogram a mode taken by `save_mode`, re-enabling it last.
+    fn restore_mode(&self, saved: &[u16; MODE_REGS.len()]) {
+        self.write(REG_ENABLE, 0);
+        for (&reg, &value) in MODE_REGS.iter().zip(saved) {
+            if reg != REG_ENABLE {
+                self.write(reg, value);
+            }
+        }
+        self.write(REG_ENABLE, saved[0]);
+    }
+}
+
+static BOCHS: Mutex<Option<Bochs>> = Mutex::new(None);
+
+/// Errors returned by `set_mode`.
+#[derive(Debug)]
+pub enum ModeError {
+    /// No Bochs adapter is bound.
+    NoDevice,
+    /// The adapter rejected the resolution.
+    InvalidMode,
+    /// The mode does not fit in VRAM.
+    OutOfVram,
+    /// The framebuffer could not be mapped.
+    MapFailed,
+    /// The console refused the new framebuffer.
+    Console(ConsoleError),
+}
+
+static DRIVER: Driver = Driver {
+    name: "bochs-vga",
+    matches: &[DeviceMatch::Pci { vendor: VENDOR_ID, device: DEVICE_ID }],
+    probe,
+};
+
+/// Register the driver with the driver model.
+pub fn register() {
+    driver::register_driver(&DRIVER);
+}
+
+fn probe(dev: &Device) -> Result<(), ProbeError> {
+    let addr = dev.pci.ok_or(ProbeError::Unsupported)?;
+    let ports = ioport::claim(DISPI_PORTS, 2, "bochs-dispi")
+        .map_err(|_| ProbeError::Failed("DISPI ports already claimed"))?;
+
+    let mut bochs = Bochs { ports, fb_phys: 0, vram_bytes: 0, fb_virt: None };
+
+    let id = bochs.read(REG_ID);
+    if id < DISPI_ID_MIN {
+        bochs.ports.release();
+        return Err(ProbeError::Unsupported);
+    }
+
+    // BAR0 is a prefetchable memory BAR; type 0b10 means 64-bit.
+    let bar0 = pci::read_bar(addr, 0);
+    let mut fb_phys = (bar0 & !0xF) as u64;
+    if (bar0 >> 1) & 0b11 == 0b10 {
+        fb_phys |= (pci::read_bar(addr, 1) as u64) << 32;
+    }
+    if fb_phys == 0 {
+        bochs.ports.release();
+        return Err(ProbeError::Failed("BAR0 not assigned"));
+    }
+
+    let vram_bytes = match bochs.read(REG_VIDEO_MEMORY_64K) {
+        0 => DEFAULT_VRAM,
+        blocks => blocks as u64 * 64 * 1024,
+    };
+    bochs.fb_phys = fb_phys;
+    bochs.vram_bytes = vram_bytes;
+
+    info!(
+        "bochs-vga: DISPI {:#x}, {} KiB VRAM at {:#x}",
+        id,
+        vram_bytes / 1024,
+        fb_phys
+    );
+    *BOCHS.lock() = Some(bochs);
+    Ok(())
+}
+
+/// Switch to `width`x`height` at 32 bpp and move the console onto it.
+pub fn set_mode(width: usize, height: usize) -> Result<(), ModeError> {
+    if width == 0 || height == 0 || width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(ModeError::InvalidMode);
+    }
+    let pitch = width * 4;
+
+    let fb = {
+        let mut guard = BOCHS.lock();
+        let bochs = guard.as_mut().ok_or(ModeError::NoDevice)?;
+        if (pitch * height) as u64 > bochs.vram_bytes {
+            return Err(ModeError::OutOfVram);
+        }
+
+        let fb_virt = match bochs.fb_virt {
+            Some(virt) => virt,
+            None => {
+                let virt = memory::map_mmio(
+                    PhysAddr::new(bochs.fb_phys),
+                    bochs.vram_bytes,
+                    PageTableFlags::WRITE_THROUGH,
+                )
+                .ok_or(ModeError::MapFailed)?;
+                bochs.fb_virt = Some(virt);
+                virt
+            }
+        };
+
+        let saved = bochs.save_mode();
+        bochs.write(REG_ENABLE, 0);
+        bochs.write(REG_XRES, width as u16);
+        bochs.write(REG_YRES, height as u16);
+        bochs.write(REG_BPP, 32);
+        bochs.write(REG_VIRT_WIDTH, width as u16);
+        bochs.write(REG_X_OFFSET, 0);
+        bochs.write(REG_Y_OFFSET, 0);
+        bochs.write(REG_ENABLE, ENABLED | LFB_ENABLED);
+
+        // The adapter clamps unsupported resolutions; treat that as a rejection
+        // and put back the mode the console is still drawing into.
+        if bochs.read(REG_XRES) as usize != width || bochs.read(REG_YRES) as usize != height {
+            bochs.restore_mode(&saved);
+            return Err(ModeError::InvalidMode);
+        }
+
+        FbInfo {
+            buffer_ptr: fb_virt.as_u64(),
+            size_bytes: bochs.vram_bytes,
+            width,
+            height,
+            pitch,
+            bytes_per_pixel: 4,
+        }
+    };
+
+    console::rebind(&fb).map_err(ModeError::Console)?;
+    events::publish(Event::DisplayModeChanged { width, height });
+    info!("bochs-vga: mode set to {}x{}x32", width, height);
+    Ok(())
+}
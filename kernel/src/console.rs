@@ -0,0 +1,86 @@
+//! Framebuffer console management (`console.rs`).
+//!
+//! - `geometry` reports the console size in text cells for the current mode.
+//! - `write_bytes` is the binary-safe output path for descriptors open on
+//!   the console: arbitrary bytes are rendered (control characters and
+//!   invalid UTF-8 made visible) and the accepted byte count is returned.
+//! - `rebind` moves the global `WRITER` onto a new framebuffer (address,
+//!   resolution or stride changed, e.g. after a modeset), keeping its colors
+//!   and scroll setting and clearing the new surface.
+//!
+//! The writer draws 32‑bit pixels only; other depths are rejected.
+
+use log::info;
+
+use crate::framebuffer::FbInfo;
+use crate::writer::{self, TextWriter, WRITER};
+
+/// Errors returned by `rebind` and `write_bytes`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConsoleError {
+    /// `writer::framebuffer_init` has not run yet.
+    NotInitialized,
+    /// Pixel size other than 4 bytes.
+    UnsupportedDepth(usize),
+    /// The mode cannot hold a single text cell, or `size_bytes` is too small
+    /// for `pitch * height`.
+    InvalidGeometry,
+}
+
+/// Console size in text cells (columns, rows), if the writer is bound.
+pub fn geometry() -> Option<(usize, usize)> {
+    let guard = WRITER.lock();
+    let w = guard.as_ref()?;
+    Some((w.width / writer::font_cell_width(), w.height / w.line_height))
+}
+
+/// Write `bytes` to the console; returns how many were accepted.
+///
+/// All bytes are accepted once the writer is bound, so callers looping on
+/// short writes terminate; before that nothing is written.
+pub fn write_bytes(bytes: &[u8]) -> Result<usize, ConsoleError> {
+    let mut guard = WRITER.lock();
+    let w = guard.as_mut().ok_or(ConsoleError::NotInitialized)?;
+    Ok(w.write_bytes(bytes))
+}
+
+/// Re-bind the console to the framebuffer described by `fb`.
+///
+/// The caller must guarantee `fb.buffer_ptr` is mapped for `fb.size_bytes`
+/// and that the old framebuffer is no longer written to.
+pub fn rebind(fb: &FbInfo) -> Result<(), ConsoleError> {
+    if fb.bytes_per_pixel != 4 {
+        return Err(ConsoleError::UnsupportedDepth(fb.bytes_per_pixel));
+    }
+    let fits = (fb.pitch * fb.height) as u64 <= fb.size_bytes
+        && fb.pitch >= fb.width * 4
+        && fb.width >= writer::font_cell_width()
+        && fb.height >= writer::font_line_height();
+    if !fits {
+        return Err(ConsoleError::InvalidGeometry);
+    }
+
+    {
+        let mut guard = WRITER.lock();
+        let old = guard.as_ref().ok_or(ConsoleError::NotInitialized)?;
+        let (fg, bg, scroll) = (old.fg_color, old.bg_color, old.enable_scroll);
+
+        let mut new =
+            unsafe { TextWriter::from_raw(fb.buffer_ptr as *mut u8, fb.width, fb.height, fb.pitch) };
+        new.set_color(fg, bg);
+        new.enable_scroll = scroll;
+
+        let bg = ((bg.0 as u32) << 16) | ((bg.1 as u32) << 8) | (bg.2 as u32);
+        for pixel in new.framebuffer.iter_mut() {
+            unsafe { core::ptr::write_volatile(pixel, bg) };
+        }
+        *guard = Some(new);
+    }
+
+    // The logger draws through WRITER, so log only after releasing it.
+    info!(
+        "console: rebound to {}x{} (pitch {}) at {:#x}",
+        fb.width, fb.height, fb.pitch, fb.buffer_ptr
+    );
+    Ok(())
+}
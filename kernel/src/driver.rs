@@ -0,0 +1,241 @@
+//! Device/driver model (`driver.rs`).
+//!
+//! - Buses (platform, PCI, virtio) register the `Device`s they discover.
+//! - Drivers register a match table and a `probe` function.
+//! - Whenever a device or driver is added, unbound devices are matched
+//!   against the registered drivers and the first successful `probe` binds them.
+//! - `lsdev` logs every known device together with its bound driver.
+//! - Registration and binding are announced on the event bus.
+//!
+//! Probing happens with the registry unlocked, so a driver's `probe` may
+//! itself register child devices (e.g. a bus bridge).
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use spin::Mutex;
+use log::{info, debug, warn};
+
+use crate::events::{self, Event};
+
+/// Bus a device was discovered on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    /// Fixed, non-enumerable devices (LAPIC, PIC, serial ports).
+    Platform,
+    /// Devices found by scanning PCI configuration space.
+    Pci,
+    /// PCI devices using the virtio vendor ID (0x1AF4).
+    Virtio,
+}
+
+impl Bus {
+    /// Short name used in listings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bus::Platform => "platform",
+            Bus::Pci => "pci",
+            Bus::Virtio => "virtio",
+        }
+    }
+}
+
+/// Location of a function in PCI configuration space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciAddress {
+    pub bus: u8,
+    pub slot: u8,
+    pub function: u8,
+}
+
+/// A device known to the kernel.
+#[derive(Debug, Clone)]
+pub struct Device {
+    /// Registry-assigned identifier.
+    pub id: usize,
+    /// Human-readable name (platform name or PCI class description).
+    pub name: &'static str,
+    pub bus: Bus,
+    /// Vendor/device IDs (zero for platform devices).
+    pub vendor: u16,
+    pub device: u16,
+    /// PCI class/subclass (zero for platform devices).
+    pub class: u8,
+    pub subclass: u8,
+    /// PCI location, if the device lives on PCI.
+    pub pci: Option<PciAddress>,
+    /// Legacy interrupt line, if any.
+    pub irq: Option<u8>,
+    /// Name of the bound driver.
+    pub driver: Option<&'static str>,
+}
+
+impl Device {
+    /// Describe a platform device with no vendor/class information.
+    pub fn platform(name: &'static str) -> Self {
+        Device {
+            id: 0,
+            name,
+            bus: Bus::Platform,
+            vendor: 0,
+            device: 0,
+            class: 0,
+            subclass: 0,
+            pci: None,
+            irq: None,
+            driver: None,
+        }
+    }
+}
+
+/// One entry in a driver's match table.
+#[derive(Debug, Clone, Copy)]
+pub enum DeviceMatch {
+    /// Platform device with the given name.
+    Platform(&'static str),
+    /// PCI/virtio device with exact vendor and device IDs.
+    Pci { vendor: u16, device: u16 },
+    /// Any PCI/virtio device of the given class and subclass.
+    PciClass { class: u8, subclass: u8 },
+}
+
+impl DeviceMatch {
+    /// Check whether this entry matches `dev`.
+    pub fn matches(&self, dev: &Device) -> bool {
+        match *self {
+            DeviceMatch::Platform(name) => dev.bus == Bus::Platform && dev.name == name,
+            DeviceMatch::Pci { vendor, device } => {
+                dev.bus != Bus::Platform && dev.vendor == vendor && dev.device == device
+            }
+            DeviceMatch::PciClass { class, subclass } => {
+                dev.bus != Bus::Platform && dev.class == class && dev.subclass == subclass
+            }
+        }
+    }
+}
+
+/// Reason a driver declined or failed to bind a device.
+#[derive(Debug)]
+pub enum ProbeError {
+    /// The driver matched but does not support this particular device.
+    Unsupported,
+    /// Initialization failed.
+    Failed(&'static str),
+}
+
+/// A driver: a name, a match table, and a probe routine.
+pub struct Driver {
+    pub name: &'static str,
+    pub matches: &'static [DeviceMatch],
+    /// Initialize the device; returning `Ok` binds it to this driver.
+    pub probe: fn(&Device) -> Result<(), ProbeError>,
+}
+
+impl Driver {
+    fn supports(&self, dev: &Device) -> bool {
+        self.matches.iter().any(|m| m.matches(dev))
+    }
+}
+
+/// Global device/driver registry.
+struct Registry {
+    devices: Vec<Device>,
+    drivers: Vec<&'static Driver>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    devices: Vec::new(),
+    drivers: Vec::new(),
+});
+
+/// Register a newly discovered device and try to bind it.
+///
+/// Returns the device's registry ID.
+pub fn register_device(mut dev: Device) -> usize {
+    let id = {
+        let mut registry = REGISTRY.lock();
+        let id = registry.devices.len();
+        dev.id = id;
+        dev.driver = None;
+        debug!("driver: registered device {} '{}' on {}", id, dev.name, dev.bus.name());
+        registry.devices.push(dev);
+        id
+    };
+
+    events::publish(Event::DeviceAdded(id));
+    bind_unbound();
+    id
+}
+
+/// Register a driver and try to bind it to existing unbound devices.
+pub fn register_driver(driver: &'static Driver) {
+    REGISTRY.lock().drivers.push(driver);
+    debug!("driver: registered driver '{}'", driver.name);
+    bind_unbound();
+}
+
+/// Match every unbound device against the registered drivers and probe.
+fn bind_unbound() {
+    // Snapshot candidate pairs so `probe` runs without the registry lock.
+    let candidates: Vec<(Device, Vec<&'static Driver>)> = {
+        let registry = REGISTRY.lock();
+        registry
+            .devices
+            .iter()
+            .filter(|dev| dev.driver.is_none())
+            .filter_map(|dev| {
+                let drivers: Vec<&'static Driver> =
+                    registry.drivers.iter().copied().filter(|d| d.supports(dev)).collect();
+                if drivers.is_empty() { None } else { Some((dev.clone(), drivers)) }
+            })
+            .collect()
+    };
+
+    for (dev, drivers) in candidates {
+        // A nested registration (from another probe) may already have bound it.
+        if REGISTRY.lock().devices[dev.id].driver.is_some() {
+            continue;
+        }
+        for driver in drivers {
+            match (driver.probe)(&dev) {
+                Ok(()) => {
+                    info!("driver: '{}' bound to device {} '{}'", driver.name, dev.id, dev.name);
+                    REGISTRY.lock().devices[dev.id].driver = Some(driver.name);
+                    events::publish(Event::DeviceBound { id: dev.id, driver: driver.name });
+                    break;
+                }
+                Err(ProbeError::Unsupported) => continue,
+                Err(ProbeError::Failed(reason)) => {
+                    warn!("driver: '{}' failed to probe device {}: {}", driver.name, dev.id, reason);
+                }
+            }
+        }
+    }
+}
+
+/// Return a snapshot of all registered devices.
+pub fn devices() -> Vec<Device> {
+    REGISTRY.lock().devices.clone()
+}
+
+/// Log every registered device and its bound driver.
+pub fn lsdev() {
+    let registry = REGISTRY.lock();
+    info!("Devices ({}):", registry.devices.len());
+    for dev in registry.devices.iter() {
+        let location = match dev.pci {
+            Some(p) => alloc::format!("{:02x}:{:02x}.{}", p.bus, p.slot, p.function),
+            None => alloc::string::String::from("-"),
+        };
+        info!(
+            "  #{:<3} {:<8} {:<8} {:04x}:{:04x} {:<24} driver={}",
+            dev.id,
+            dev.bus.name(),
+            location,
+            dev.vendor,
+            dev.device,
+            dev.name,
+            dev.driver.unwrap_or("(none)")
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Allocation-free output for panic, early-boot and fault paths (`emergency.rs`).
+//!
+//! - Writes straight to COM1 with polled I/O: no heap, no locks, no logger.
+//! - `dec`/`hex` format integers into caller-provided stack buffers (itoa-style).
+//! - `drain` waits for a UART's transmitter to empty before the machine stops.
+//! - `EmergencyWriter` implements `fmt::Write` for `write!` with `core::fmt`
+//!   arguments, which never allocate.
+//!
+//! Use this wherever the heap or a lock may be the thing that failed: the
+//! panic handler, double-fault and NMI handlers, and code that runs before
+//! the logger is up. The regular logger must not be called from there.
+
+use core::fmt;
+use x86_64::instructions::port::Port;
+
+use crate::serial::COM1;
+
+/// Line status register offset and its transmit-holding-empty bit.
+const LINE_STATUS: u16 = 5;
+const THR_EMPTY: u8 = 0x20;
+
+/// Give up waiting for the UART after this many polls (e.g. no COM1 present).
+const TX_SPIN_LIMIT: u32 = 100_000;
+
+/// Write one byte to COM1, waiting (boundedly) for the transmitter.
+pub fn write_byte(byte: u8) {
+    unsafe {
+        let mut status: Port<u8> = Port::new(COM1 + LINE_STATUS);
+        let mut spins = 0;
+        while status.read() & THR_EMPTY == 0 && spins < TX_SPIN_LIMIT {
+            core::hint::spin_loop();
+            spins += 1;
+        }
+        Port::new(COM1).write(byte);
+    }
+}
+
+/// Line status bit: transmit holding and shift registers both empty.
+const TX_IDLE: u8 = 0x40;
+
+/// Wait (boundedly) until the UART at `base` has shifted out every byte it
+/// was given, so a following halt or VM exit does not cut the output short.
+pub fn drain(base: u16) {
+    unsafe {
+        let mut status: Port<u8> = Port::new(base + LINE_STATUS);
+        let mut spins = 0;
+        while status.read() & TX_IDLE == 0 && spins < TX_SPIN_LIMIT {
+            core::hint::spin_loop();
+            spins += 1;
+        }
+    }
+}
+
+/// Write a string to COM1.
+pub fn print(s: &str) {
+    for byte in s.bytes() {
+        write_byte(byte);
+    }
+}
+
+/// Format `value` in decimal into `buf`, returning the digits.
+pub fn dec(value: u64, buf: &mut [u8; 20]) -> &str {
+    let mut value = value;
+    let mut pos = buf.len();
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    // Only ASCII digits were written.
+    unsafe { core::str::from_utf8_unchecked(&buf[pos..]) }
+}
+
+/// Format `value` as `0x`-prefixed, zero-padded hexadecimal into `buf`.
+pub fn hex(value: u64, buf: &mut [u8; 18]) -> &str {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    buf[0] = b'0';
+    buf[1] = b'x';
+    for i in 0..16 {
+        buf[17 - i] = DIGITS[((value >> (i * 4)) & 0xF) as usize];
+    }
+    unsafe { core::str::from_utf8_unchecked(&buf[..]) }
+}
+
+/// Print `value` in decimal.
+pub fn print_dec(value: u64) {
+    print(dec(value, &mut [0; 20]));
+}
+
+/// Print `value` in hexadecimal.
+pub fn print_hex(value: u64) {
+    print(hex(value, &mut [0; 18]));
+}
+
+/// `fmt::Write` sink over COM1; never allocates or locks.
+pub struct EmergencyWriter;
+
+impl fmt::Write for EmergencyWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        print(s);
+        Ok(())
+    }
+}
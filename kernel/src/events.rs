@@ -0,0 +1,64 @@
+//! Kernel event bus (`events.rs`).
+//!
+//! - Subsystems `subscribe` a handler; producers `publish` an `Event`.
+//! - Every handler receives every event and ignores the ones it doesn't care about.
+//! - Lets drivers announce devices without knowing who reacts (logger, future VFS/devfs).
+//!
+//! The subscriber table is a fixed-size array, so subscribing works before
+//! the heap exists. Handlers run synchronously on the publisher's stack with
+//! the table unlocked, so a handler may publish further events.
+//! Do not publish from interrupt handlers: subscribing code may hold the table lock.
+
+use spin::Mutex;
+use log::error;
+
+/// Maximum number of subscribers.
+const MAX_SUBSCRIBERS: usize = 16;
+
+/// Notifications published on the bus.
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    /// A device was registered with the driver model (registry ID).
+    DeviceAdded(usize),
+    /// A device was bound to a driver.
+    DeviceBound { id: usize, driver: &'static str },
+    /// A device disappeared (registry ID).
+    DeviceRemoved(usize),
+    /// A block device became available (registry ID).
+    DiskAttached(usize),
+    /// A block device went away (registry ID).
+    DiskDetached(usize),
+    /// The display switched resolution and the console was rebound.
+    DisplayModeChanged { width: usize, height: usize },
+}
+
+/// Event handler signature.
+pub type Handler = fn(&Event);
+
+/// A named subscriber (the name is only used for diagnostics).
+#[derive(Clone, Copy)]
+struct Subscriber {
+    name: &'static str,
+    handler: Handler,
+}
+
+static SUBSCRIBERS: Mutex<[Option<Subscriber>; MAX_SUBSCRIBERS]> =
+    Mutex::new([None; MAX_SUBSCRIBERS]);
+
+/// Register `handler` to receive all future events.
+pub fn subscribe(name: &'static str, handler: Handler) {
+    let mut subscribers = SUBSCRIBERS.lock();
+    match subscribers.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => *slot = Some(Subscriber { name, handler }),
+        None => error!("events: subscriber table full, '{}' not registered", name),
+    }
+}
+
+/// Deliver `event` to every subscriber.
+pub fn publish(event: Event) {
+    // Copy the table so handlers run without the lock held.
+    let subscribers = *SUBSCRIBERS.lock();
+    for subscriber in subscribers.iter().flatten() {
+        (subscriber.handler)(&event);
+    }
+}
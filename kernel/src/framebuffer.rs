@@ -13,6 +13,8 @@ pub struct FbInfo {
     pub height: usize,
     /// Bytes per row (stride × bytes_per_pixel).
     pub pitch: usize,
+    /// Bytes per pixel (the console only draws 4‑byte pixels).
+    pub bytes_per_pixel: usize,
 }
 
 /// KernelFramebuffer wraps the bootloader framebuffer.
@@ -88,6 +90,7 @@ pub fn boot_fb_info(boot_info: &BootInfo) -> Option<FbInfo> {
             width: info.width,
             height: info.height,
             pitch: info.stride * info.bytes_per_pixel,
+            bytes_per_pixel: info.bytes_per_pixel,
         }
     })
 }
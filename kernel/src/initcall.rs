@@ -0,0 +1,96 @@
+//! Dependency-ordered initcalls (`initcall.rs`).
+//!
+//! - Each `Initcall` names itself and the initcalls it depends on.
+//! - `run` topologically sorts the table and executes it in dependency order.
+//! - Missing dependencies and cycles are reported by name before anything
+//!   runs, instead of surfacing later as an unrelated panic.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use log::{info, debug, error};
+
+/// A named initialization step with declared dependencies.
+pub struct Initcall {
+    pub name: &'static str,
+    /// Names of initcalls that must complete first.
+    pub deps: &'static [&'static str],
+    pub init: fn(),
+}
+
+/// Errors detected while ordering an initcall table.
+#[derive(Debug)]
+pub enum InitError {
+    /// `initcall` depends on `missing`, which is not in the table.
+    MissingDependency { initcall: &'static str, missing: &'static str },
+    /// Two initcalls share a name.
+    Duplicate(&'static str),
+    /// The listed initcalls form (or wait on) a dependency cycle.
+    Cycle(Vec<&'static str>),
+}
+
+/// Compute an execution order for `calls` (indices into the slice).
+pub fn order(calls: &[Initcall]) -> Result<Vec<usize>, InitError> {
+    let index_of = |name: &str| calls.iter().position(|c| c.name == name);
+
+    for (i, call) in calls.iter().enumerate() {
+        if index_of(call.name) != Some(i) {
+            return Err(InitError::Duplicate(call.name));
+        }
+        for &dep in call.deps {
+            if index_of(dep).is_none() {
+                return Err(InitError::MissingDependency { initcall: call.name, missing: dep });
+            }
+        }
+    }
+
+    // Kahn's algorithm; tables are small, so a quadratic scan is fine.
+    let mut done = alloc::vec![false; calls.len()];
+    let mut ordered = Vec::with_capacity(calls.len());
+
+    while ordered.len() < calls.len() {
+        let ready = (0..calls.len()).find(|&i| {
+            !done[i] && calls[i].deps.iter().all(|dep| done[index_of(dep).unwrap()])
+        });
+
+        match ready {
+            Some(i) => {
+                done[i] = true;
+                ordered.push(i);
+            }
+            None => {
+                let stuck = (0..calls.len()).filter(|&i| !done[i]).map(|i| calls[i].name).collect();
+                return Err(InitError::Cycle(stuck));
+            }
+        }
+    }
+
+    Ok(ordered)
+}
+
+/// Run every initcall in `calls` in dependency order.
+///
+/// Nothing runs if the table is inconsistent; the error names the culprit.
+pub fn run(calls: &[Initcall]) -> Result<(), InitError> {
+    let ordered = match order(calls) {
+        Ok(ordered) => ordered,
+        Err(e) => {
+            match &e {
+                InitError::MissingDependency { initcall, missing } => {
+                    error!("initcall '{}' depends on '{}', which is not registered", initcall, missing)
+                }
+                InitError::Duplicate(name) => error!("initcall '{}' registered twice", name),
+                InitError::Cycle(names) => error!("initcall dependency cycle among {:?}", names),
+            }
+            return Err(e);
+        }
+    };
+
+    for i in ordered {
+        debug!("initcall: running '{}'", calls[i].name);
+        (calls[i].init)();
+    }
+
+    info!("initcalls complete ({} run)", calls.len());
+    Ok(())
+}
@@ -5,6 +5,8 @@ use log::{info, error};
 use crate::apic::send_eoi;
 use core::sync::atomic::{AtomicUsize, AtomicU64};
 use crate::time::tick;
+use crate::emergency;
+use crate::process;
 
 /// LAPIC timer interrupt vector.
 pub const LAPIC_TIMER_VECTOR: u8 = 0x31;
@@ -121,7 +123,9 @@ extern "x86-interrupt" fn debug_handler(stack_frame: InterruptStackFrame) {
 }
 
 extern "x86-interrupt" fn non_maskable_interrupt_handler(stack_frame: InterruptStackFrame) {
-    error!("EXCEPTION: NON MASKABLE\n{:#?}", stack_frame);
+    // NMIs can arrive with the logger or heap locked: use the emergency path.
+    emergency::print("EXCEPTION: NON MASKABLE");
+    print_frame(&stack_frame);
     panic!("EXCEPTION: NON MASKABLE");
 }
 
@@ -154,21 +158,47 @@ extern "x86-interrupt" fn page_fault_handler(
     error_code: PageFaultErrorCode,
 ) {
     use x86_64::registers::control::Cr2;
+    let addr = Cr2::read();
+
+    // Not-present faults inside a process VMA are demand paging, not bugs.
+    let fault = process::handle_page_fault(
+        addr,
+        error_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION),
+        error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE),
+        error_code.contains(PageFaultErrorCode::INSTRUCTION_FETCH),
+    );
+    let Err(reason) = fault else { return };
+
     error!("EXCEPTION: PAGE FAULT");
-    error!("Accessed Address: {:?}", Cr2::read());
+    error!("Accessed Address: {:?}", addr);
     error!("Error Code: {:?}", error_code);
+    error!("Unresolved: {:?}", reason);
     error!("{:#?}", stack_frame);
     panic!("EXCEPTION: PAGE FAULT");
 }
 
 extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame,
-    _error_code: u64,
+    error_code: u64,
 ) -> ! {
-    error!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
+    // The fault may have come from the allocator or logger: use the emergency path.
+    emergency::print("EXCEPTION: DOUBLE FAULT, error code ");
+    emergency::print_hex(error_code);
+    print_frame(&stack_frame);
     panic!("EXCEPTION: DOUBLE FAULT");
 }
 
+/// Print the interesting parts of an exception frame without allocating.
+fn print_frame(stack_frame: &InterruptStackFrame) {
+    emergency::print("\n  rip=");
+    emergency::print_hex(stack_frame.instruction_pointer.as_u64());
+    emergency::print(" rsp=");
+    emergency::print_hex(stack_frame.stack_pointer.as_u64());
+    emergency::print(" rflags=");
+    emergency::print_hex(stack_frame.cpu_flags);
+    emergency::print("\n");
+}
+
 extern "x86-interrupt" fn invalid_tss_handler(stack_frame: InterruptStackFrame, _error_code: u64) {
     error!("EXCEPTION: INVALID TSS\n{:#?}", stack_frame);
     panic!("EXCEPTION: INVALID TSS");
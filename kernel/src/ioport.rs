@@ -0,0 +1,157 @@
+//! I/O port ownership tracking (`ioport.rs`).
+//!
+//! - Drivers claim a contiguous range of ports before touching them.
+//! - Overlapping claims are rejected and logged, naming the current owner.
+//! - Claimed ranges hand out typed, bounds-checked `read`/`write` helpers.
+//! - `log_ioports` prints the claim table in the style of Linux `/proc/ioports`.
+//!
+//! The claim table is a fixed-size array so ports can be claimed in early boot,
+//! before the heap is initialized (e.g. when masking the legacy PIC).
+
+use spin::Mutex;
+use x86_64::instructions::port::{Port, PortRead, PortWrite};
+use log::{info, error};
+
+/// Maximum number of simultaneously claimed port ranges.
+const MAX_CLAIMS: usize = 32;
+
+/// A claimed, contiguous range of I/O ports.
+#[derive(Debug, Clone, Copy)]
+struct Claim {
+    start: u16,
+    len: u16,
+    owner: &'static str,
+}
+
+impl Claim {
+    /// Last port (inclusive) covered by this claim.
+    fn end(&self) -> u16 {
+        self.start + (self.len - 1)
+    }
+
+    fn overlaps(&self, start: u16, len: u16) -> bool {
+        let end = start + (len - 1);
+        start <= self.end() && self.start <= end
+    }
+}
+
+/// Global claim table.
+static CLAIMS: Mutex<[Option<Claim>; MAX_CLAIMS]> = Mutex::new([None; MAX_CLAIMS]);
+
+/// Errors returned when claiming a port range.
+#[derive(Debug)]
+pub enum IoPortError {
+    /// The range is empty or wraps past port 0xFFFF.
+    InvalidRange,
+    /// Part of the range is already owned by another driver.
+    Conflict { owner: &'static str },
+    /// The claim table is full.
+    TableFull,
+}
+
+/// Handle to a claimed port range.
+///
+/// Offsets passed to `read`/`write` are relative to the start of the range
+/// and are checked against its length.
+#[derive(Debug)]
+pub struct IoPortRange {
+    start: u16,
+    len: u16,
+    owner: &'static str,
+}
+
+/// Claim `len` ports starting at `start` for `owner`.
+///
+/// Fails (and logs an error naming both parties) if any port in the range
+/// is already claimed.
+pub fn claim(start: u16, len: u16, owner: &'static str) -> Result<IoPortRange, IoPortError> {
+    if len == 0 || start.checked_add(len - 1).is_none() {
+        error!("ioport: {} requested invalid range {:#06x}+{}", owner, start, len);
+        return Err(IoPortError::InvalidRange);
+    }
+
+    let mut claims = CLAIMS.lock();
+
+    if let Some(existing) = claims.iter().flatten().find(|c| c.overlaps(start, len)) {
+        error!(
+            "ioport: {} cannot claim {:#06x}-{:#06x}: already owned by {} ({:#06x}-{:#06x})",
+            owner,
+            start,
+            start + (len - 1),
+            existing.owner,
+            existing.start,
+            existing.end()
+        );
+        return Err(IoPortError::Conflict { owner: existing.owner });
+    }
+
+    match claims.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => {
+            *slot = Some(Claim { start, len, owner });
+            Ok(IoPortRange { start, len, owner })
+        }
+        None => {
+            error!("ioport: claim table full, {} not registered", owner);
+            Err(IoPortError::TableFull)
+        }
+    }
+}
+
+impl IoPortRange {
+    /// First port of the range.
+    pub fn base(&self) -> u16 {
+        self.start
+    }
+
+    /// Name of the driver owning the range.
+    pub fn owner(&self) -> &'static str {
+        self.owner
+    }
+
+    /// Read a value of type `T` from `base + offset`.
+    pub fn read<T: PortRead>(&self, offset: u16) -> T {
+        let port = self.port(offset);
+        unsafe { Port::<T>::new(port).read() }
+    }
+
+    /// Write a value of type `T` to `base + offset`.
+    pub fn write<T: PortWrite>(&self, offset: u16, value: T) {
+        let port = self.port(offset);
+        unsafe { Port::<T>::new(port).write(value) }
+    }
+
+    /// Give the range back so another driver may claim it.
+    pub fn release(self) {
+        let mut claims = CLAIMS.lock();
+        for slot in claims.iter_mut() {
+            if matches!(slot, Some(c) if c.start == self.start && c.len == self.len) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Resolve `offset` to an absolute port, panicking if it leaves the range.
+    fn port(&self, offset: u16) -> u16 {
+        assert!(
+            offset < self.len,
+            "ioport: {} accessed offset {:#x} outside its {}-port range",
+            self.owner,
+            offset,
+            self.len
+        );
+        self.start + offset
+    }
+}
+
+/// Log all claimed port ranges, sorted by start port.
+///
+/// Output mirrors Linux `/proc/ioports`: `0020-0021 : pic1`.
+pub fn log_ioports() {
+    let mut claims = *CLAIMS.lock();
+    claims.sort_unstable_by_key(|c| c.map_or(u16::MAX, |c| c.start));
+
+    info!("I/O port claims:");
+    for claim in claims.iter().flatten() {
+        info!("  {:04x}-{:04x} : {}", claim.start, claim.end(), claim.owner);
+    }
+}
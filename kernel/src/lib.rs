@@ -36,6 +36,7 @@ pub mod framebuffer;
 pub mod interrupts;
 pub mod gdt;
 pub mod allocator;
+pub use bulldog_collections as collections;
 pub mod memory;
 pub mod stack;
 pub mod apic;
@@ -43,10 +44,42 @@ pub mod time;
 pub mod font;
 pub mod color;
 pub mod logger;
+pub mod ioport;
+pub mod driver;
+pub mod pci;
+pub mod initcall;
+pub mod events;
+pub mod preempt;
+pub mod rcu;
+pub mod sysctl;
+pub mod serial;
+pub mod net;
+pub mod rtc;
+pub mod emergency;
+pub mod console;
+pub mod bochs;
+pub mod process;
+pub mod audit;
 
 use crate::allocator::ALLOCATOR;
 use crate::apic::{lapic_read, LapicRegister, setup_apic};
 use crate::memory::{BootInfoFrameAllocator, PreHeapAllocator, init_offset_page_table, map_lapic_mmio};
+use crate::initcall::Initcall;
+
+/// Late initcalls, run once paging, heap and APIC are up.
+/// Order is derived from `deps`, not from position in this table.
+const INITCALLS: &[Initcall] = &[
+    Initcall { name: "platform-devices", deps: &[], init: register_platform_devices },
+    Initcall { name: "pci", deps: &[], init: pci::enumerate },
+    Initcall { name: "lsdev", deps: &["platform-devices", "pci", "bochs-vga"], init: driver::lsdev },
+    Initcall { name: "ioports", deps: &["pci", "bochs-vga"], init: ioport::log_ioports },
+    Initcall { name: "health", deps: &[], init: start_health_check },
+    Initcall { name: "timekeeping", deps: &[], init: time::calibrate },
+    Initcall { name: "bochs-vga", deps: &[], init: bochs::register },
+    Initcall { name: "process", deps: &[], init: process::init },
+    Initcall { name: "fault-reserve", deps: &[], init: memory::start_fault_reserve },
+    Initcall { name: "wx-audit", deps: &["bochs-vga", "process"], init: memory::wx::wx_audit },
+];
 
 /// Kernel initialization routine.
 /// 
@@ -91,8 +124,8 @@ pub fn kernel_init(
     info!("Heap initialized");
 
     info!("Finalizing frame allocator from temp allocator");
-    let frames = temp_allocator.into_vec();
-    let mut frame_allocator = BootInfoFrameAllocator::new(memory_map, frames);
+    let heap_frames: Vec<PhysFrame> = temp_allocator.used_frames().collect();
+    let mut frame_allocator = BootInfoFrameAllocator::new(memory_map, &heap_frames);
     info!("Frame allocator ready");
 
     // Optional: identity-map framebuffer region here if needed
@@ -118,7 +151,7 @@ pub fn kernel_init(
     info!("Mapping LAPIC IST stack");
     let lapic_stack_start = VirtAddr::from_ptr(unsafe { core::ptr::addr_of!(stack::LAPIC_STACK.0) });
     let lapic_stack_end = lapic_stack_start + gdt::STACK_SIZE;
-    let lapic_stack_range = Page::range_inclusive(
+    let lapic_stack_range = Page::<Size4KiB>::range_inclusive(
         Page::containing_address(lapic_stack_start),
         Page::containing_address(lapic_stack_end - 1u64),
     );
@@ -140,33 +173,27 @@ pub fn kernel_init(
     }
 
     debug!("LAPIC stack range: virt={:#x} - {:#x}", lapic_stack_start, lapic_stack_end);
-    for page in lapic_stack_range {
-        match mapper.translate_addr(page.start_address()) {
-            Some(phys) => {
-                let frame = PhysFrame::containing_address(phys);
-                let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
-
-                debug!("Ensure flags: remapping page {:?}", page.start_address());
-                unsafe {
-                    mapper.unmap(page)
-                        .map_err(|_| MapToError::FrameAllocationFailed)?
-                        .1
-                        .flush();
-
-                    mapper.map_to(page, frame, flags, &mut frame_allocator)?.flush();
-                }
-            }
-            None => {
-                error!(
-                    "No translation for LAPIC stack page {:?}; supply a backing PhysFrame from allocator",
-                    page.start_address()
-                );
-            }
-        }
-    }
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+    let remapped = memory::protect_range(
+        &mut mapper,
+        lapic_stack_start..lapic_stack_end,
+        flags,
+        &mut frame_allocator,
+    )?;
+    debug!("Ensure flags: remapped {} LAPIC stack pages", remapped);
+
+    // Paint IST stacks now that they are writable and not yet in use.
+    unsafe { stack::paint_ist_stacks(); }
 
     setup_apic();
 
+    // Hand the frame allocator over to the rest of the kernel before the
+    // initcalls: drivers map MMIO, allocate DMA and build address spaces.
+    *memory::FRAME_ALLOCATOR.lock() = Some(frame_allocator);
+    memory::log_meminfo(&memory::stats());
+
+    initcall::run(INITCALLS).expect("initcall table is inconsistent");
+
     let count = lapic_read(LapicRegister::CURRENT_COUNT);
     info!("LAPIC CURRENT COUNT: {}", count);
 
@@ -177,15 +204,30 @@ pub fn kernel_init(
     Ok(())
 }
 
+/// Register fixed platform devices with the driver model.
+fn register_platform_devices() {
+    driver::register_device(driver::Device::platform("pic8259"));
+    driver::register_device(driver::Device::platform("lapic"));
+}
+
+/// Schedule the periodic health check at `kernel.health_interval`.
+fn start_health_check() {
+    let period = time::HEALTH_INTERVAL.load(core::sync::atomic::Ordering::Relaxed);
+    if let Err(e) = time::interval(time::HEALTH_TASK, period, time::health_check) {
+        error!("health check not scheduled: {:?}", e);
+    }
+}
+
 /// Disable legacy PIC by masking all IRQs.
 /// Ensures APIC is the sole interrupt controller.
+/// Claims both PIC port ranges so no other driver can reprogram them.
 pub fn disable_pic() {
-    unsafe {
-        let mut pic1 = x86_64::instructions::port::Port::new(0x21);
-        let mut pic2 = x86_64::instructions::port::Port::new(0xA1);
-        pic1.write(0xFFu8);
-        pic2.write(0xFFu8);
-    }
+    let pic1 = ioport::claim(0x20, 2, "pic1").expect("PIC1 ports already claimed");
+    let pic2 = ioport::claim(0xA0, 2, "pic2").expect("PIC2 ports already claimed");
+
+    // Offset 1 is the data (mask) register.
+    pic1.write(1, 0xFFu8);
+    pic2.write(1, 0xFFu8);
 }
 
 /// Allocator error handler.
@@ -193,14 +235,32 @@ pub fn disable_pic() {
 #[alloc_error_handler]
 fn alloc_error_handler(layout: alloc::alloc::Layout) -> ! {
     error!("PANIC: allocation error — size: {}, align: {}", layout.size(), layout.align());
+    match memory::try_stats() {
+        Some(stats) => memory::log_meminfo(&stats),
+        None => error!("meminfo unavailable: allocator locked"),
+    }
     panic!("allocation error: {:?}", layout)
 }
 
+/// Terminal halt for the panic path: interrupts off, `hlt` forever.
+///
+/// Unlike `hlt_loop` it runs no preemption check, watchdog, RCU or interval
+/// work; those take locks, allocate, or (`might_sleep`) panic again.
+pub fn halt_forever() -> ! {
+    x86_64::instructions::interrupts::disable();
+    loop {
+        // NMIs still wake `hlt`; go straight back to sleep.
+        x86_64::instructions::hlt();
+    }
+}
+
 /// Halt loop: the kernel’s idle routine.
 /// 
 /// - Puts the CPU into a low‑power state (`hlt`) until the next interrupt.
 /// - Uses a watchdog to detect stalls in the tick counter.
-/// - Runs a periodic health check to log kernel liveness.
+/// - Runs due periodic tasks (`time::interval`), including the health check.
+/// - Asserts preemption is enabled before each halt (halting is sleeping).
+/// - Reports an RCU quiescent state so retired values can be reclaimed.
 /// 
 /// Safety: must only be called once interrupts and the LAPIC timer are configured.
 /// Otherwise the CPU will halt indefinitely without waking.
@@ -208,9 +268,11 @@ pub fn hlt_loop() -> ! {
     let mut wd = crate::time::Watchdog::new(5000u64, 3u32, 2u32);
 
     loop {
+        crate::preempt::might_sleep();
         unsafe { core::arch::asm!("hlt"); }
         wd.check();
-        crate::time::health_check(1000);
+        crate::rcu::quiescent();
+        crate::time::run_intervals();
     }
 }
 
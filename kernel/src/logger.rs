@@ -1,14 +1,166 @@
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::fmt::{Arguments, Write};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use log::{self, Level, LevelFilter, Metadata, Record, set_max_level};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
 
 use crate::writer::WRITER;
+use crate::emergency::{self, EmergencyWriter};
+use crate::serial::{self, Channel, StackBuf, COM1, COM2};
+use crate::events::{self, Event};
 
 /// Tracks the current maximum log level filter.
 /// Stored as an atomic so it can be updated safely at runtime.
 pub static CURRENT_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
 
+/// Set by `flush_all`: records skip the framebuffer, the multiplexer and the
+/// rate limiter (whose locks the crashed code may hold) and go to COM1.
+static EMERGENCY: AtomicBool = AtomicBool::new(false);
+
+/// Messages one source (log target) may emit per second before further
+/// ones are suppressed (`kernel.log_burst`; 0 disables rate limiting).
+pub static LOG_BURST: AtomicU64 = AtomicU64::new(100);
+
+/// Panic instead of suppressing when a source exceeds its burst
+/// (`kernel.log_flood_panic`), to catch the offender in the act.
+pub static FLOOD_PANIC: AtomicBool = AtomicBool::new(false);
+
+/// Sources tracked at once; colliding sources share a slot and evict each other.
+const MAX_SOURCES: usize = 32;
+
+/// Bytes of the source name kept for suppression summaries.
+const SOURCE_NAME_LEN: usize = 48;
+
+/// Rate-limit state of one log source in the current window.
+#[derive(Clone, Copy)]
+struct Source {
+    hash: u64,
+    name: [u8; SOURCE_NAME_LEN],
+    name_len: usize,
+    window_start: u64,
+    count: u64,
+    suppressed: u64,
+}
+
+impl Source {
+    fn new(hash: u64, target: &str, now: u64) -> Self {
+        let name_len = target.len().min(SOURCE_NAME_LEN);
+        let mut name = [0; SOURCE_NAME_LEN];
+        name[..name_len].copy_from_slice(&target.as_bytes()[..name_len]);
+        Source { hash, name, name_len, window_start: now, count: 0, suppressed: 0 }
+    }
+
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("?")
+    }
+}
+
+static SOURCES: Mutex<[Option<Source>; MAX_SOURCES]> = Mutex::new([None; MAX_SOURCES]);
+
+/// FNV-1a hash of a log target.
+fn source_hash(target: &str) -> u64 {
+    target.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+/// Length of a rate-limit window in ticks (one second).
+fn window_ticks() -> u64 {
+    crate::time::tick_hz().max(1)
+}
+
+/// Count a message from `target` against its burst.
+///
+/// Returns whether it may be emitted, and a source whose suppressed
+/// messages are due to be summarized (its window ended or it was evicted).
+/// Before the timer starts nothing is limited, so early boot logs intact.
+fn admit(target: &str) -> (bool, Option<Source>) {
+    let burst = LOG_BURST.load(Ordering::Relaxed);
+    let now = crate::time::get_ticks();
+    if burst == 0 || now == 0 {
+        return (true, None);
+    }
+    let hash = source_hash(target);
+
+    interrupts::without_interrupts(|| {
+        let mut sources = SOURCES.lock();
+        let slot = &mut sources[(hash % MAX_SOURCES as u64) as usize];
+        let mut report = None;
+        match slot {
+            Some(source) if source.hash == hash => {
+                if now.saturating_sub(source.window_start) >= window_ticks() {
+                    report = Some(*source).filter(|s| s.suppressed > 0);
+                    *source = Source::new(hash, target, now);
+                }
+            }
+            _ => {
+                report = slot.filter(|s| s.suppressed > 0);
+                *slot = Some(Source::new(hash, target, now));
+            }
+        }
+
+        let source = slot.as_mut().unwrap();
+        if source.count < burst {
+            source.count += 1;
+            (true, report)
+        } else {
+            source.suppressed += 1;
+            (false, report)
+        }
+    })
+}
+
+fn log_suppressed(source: &Source) {
+    emit(
+        Level::Warn,
+        format_args!("logger: {} message(s) from {} suppressed", source.suppressed, source.name()),
+    );
+}
+
+/// Summarize sources whose window has ended with messages still suppressed.
+///
+/// A flooding source that then goes quiet would otherwise never report;
+/// run periodically from the health check.
+pub fn flush_suppressed() {
+    let now = crate::time::get_ticks();
+    loop {
+        // Take one due source at a time so the lock is never held while drawing.
+        let due = interrupts::without_interrupts(|| {
+            let mut sources = SOURCES.lock();
+            let source = sources.iter_mut().flatten().find(|s| {
+                s.suppressed > 0 && now.saturating_sub(s.window_start) >= window_ticks()
+            })?;
+            let due = *source;
+            source.suppressed = 0;
+            Some(due)
+        });
+        match due {
+            Some(source) => log_suppressed(&source),
+            None => break,
+        }
+    }
+}
+
+/// Draw a line on the framebuffer console and mirror it onto the serial
+/// multiplexer's log channel, bypassing filtering and rate limiting.
+fn emit(level: Level, args: Arguments) {
+    if let Some(w) = WRITER.lock().as_mut() {
+        let lvl = match level {
+            Level::Error => crate::writer::LogLevel::Error,
+            Level::Warn  => crate::writer::LogLevel::Warn,
+            Level::Info  => crate::writer::LogLevel::Info,
+            Level::Debug => crate::writer::LogLevel::Debug,
+            Level::Trace => crate::writer::LogLevel::Trace,
+        };
+        w.log(lvl, format_args!("{}", args));
+    }
+
+    let mut line = StackBuf::<256>::new();
+    let _ = write!(line, "[{:<5}] {}\n", level, args);
+    serial::mux_send(Channel::Log, line.as_bytes());
+}
+
 /// Bulldog’s custom logger implementation.
-/// Routes log records into the kernel’s framebuffer writer.
+/// Routes log records into the kernel’s framebuffer writer
+/// and mirrors them onto the serial multiplexer's log channel.
 struct BulldogLogger;
 
 /// Global logger instance registered with the `log` crate.
@@ -21,26 +173,45 @@ impl log::Log for BulldogLogger {
     }
 
     /// Handles an incoming log record.
-    /// Converts the `log::Level` into Bulldog’s internal `LogLevel` and forwards
-    /// the formatted message to the global writer.
+    /// Applies the per-source rate limit, then forwards the message to the
+    /// framebuffer writer and the serial log channel.
     fn log(&self, record: &Record) {
         if !self.enabled(record.metadata()) {
             return;
         }
-        if let Some(w) = WRITER.lock().as_mut() {
-            let lvl = match record.level() {
-                Level::Error => crate::writer::LogLevel::Error,
-                Level::Warn  => crate::writer::LogLevel::Warn,
-                Level::Info  => crate::writer::LogLevel::Info,
-                Level::Debug => crate::writer::LogLevel::Debug,
-                Level::Trace => crate::writer::LogLevel::Trace,
-            };
-            w.log(lvl, format_args!("{}", record.args()));
+        if EMERGENCY.load(Ordering::Relaxed) {
+            let _ = write!(EmergencyWriter, "[{:<5}] {}\n", record.level(), record.args());
+            return;
+        }
+        let (allowed, report) = admit(record.target());
+        if let Some(source) = report {
+            log_suppressed(&source);
+        }
+        if !allowed {
+            if FLOOD_PANIC.load(Ordering::Relaxed) {
+                panic!("logger: {} exceeded {} messages/s", record.target(), LOG_BURST.load(Ordering::Relaxed));
+            }
+            return;
         }
+        emit(record.level(), *record.args());
+    }
+
+    /// Records are written synchronously; wait for the UARTs to send them.
+    fn flush(&self) {
+        emergency::drain(COM2);
+        emergency::drain(COM1);
     }
+}
 
-    /// Flush is a no‑op because Bulldog’s writer logs directly to framebuffer.
-    fn flush(&self) {}
+/// Make sure everything logged so far has left the machine, and switch the
+/// logger to the lock-free emergency sink (COM1) from here on.
+///
+/// Call before halting, rebooting or exiting the VM; the panic handler does.
+/// A multiplexer frame the crash interrupted stays truncated and is
+/// discarded by the host demultiplexer.
+pub fn flush_all() {
+    EMERGENCY.store(true, Ordering::SeqCst);
+    log::logger().flush();
 }
 
 /// Initialize Bulldog’s logger at the given level.
@@ -56,10 +227,31 @@ pub fn logger_init(level: LevelFilter) {
     }
     set_max_level(level);
     CURRENT_LEVEL.store(level as usize, Ordering::Relaxed);
+    events::subscribe("logger", log_event);
 
     log::info!("Logger initialized at {:?} level", level);
 }
 
+/// Change the maximum log level at runtime (used by `kernel.loglevel`).
+pub fn set_level(level: LevelFilter) {
+    set_max_level(level);
+    CURRENT_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Event bus subscriber: records device lifecycle events in the log.
+fn log_event(event: &Event) {
+    match event {
+        Event::DeviceAdded(id) => log::debug!("event: device {} added", id),
+        Event::DeviceBound { id, driver } => log::debug!("event: device {} bound to '{}'", id, driver),
+        Event::DeviceRemoved(id) => log::info!("event: device {} removed", id),
+        Event::DiskAttached(id) => log::info!("event: disk {} attached", id),
+        Event::DiskDetached(id) => log::info!("event: disk {} detached", id),
+        Event::DisplayModeChanged { width, height } => {
+            log::debug!("event: display mode changed to {}x{}", width, height)
+        }
+    }
+}
+
 
 
 
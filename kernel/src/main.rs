@@ -19,8 +19,6 @@ use bootloader_api::{
     info::BootInfo,
 };
 use core::panic::PanicInfo;
-use x86_64::instructions::port::Port;
-use alloc::string::ToString;
 
 use kernel::{
     framebuffer::KernelFramebuffer,
@@ -28,6 +26,7 @@ use kernel::{
     font::get_glyph,
     color::*,
     hlt_loop,
+    emergency::{self, EmergencyWriter},
     logger::logger_init,
     kernel_init,
 };
@@ -41,11 +40,14 @@ use x86_64::VirtAddr;
 /// - Kernel stack size: 100 KiB
 /// - Physical memory mapping: dynamic
 /// - Framebuffer mapping: dynamic
+/// - Dynamic mappings placed in the upper half, keeping the user window
+///   (`memory::addr::USER_SPACE_START..USER_SPACE_END`) free
 const CONFIG: BootloaderConfig = {
     let mut config = BootloaderConfig::new_default();
     config.kernel_stack_size = 100 * 1024;
     config.mappings.physical_memory = Some(Mapping::Dynamic);
     config.mappings.framebuffer = Mapping::Dynamic;
+    config.mappings.dynamic_range_start = Some(0xFFFF_8000_0000_0000);
     config
 };
 
@@ -60,6 +62,11 @@ const CONFIG: BootloaderConfig = {
 entry_point!(kernel_main, config = &CONFIG);
 
 fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
+    // Nothing is initialized yet: only the allocation-free path is usable.
+    emergency::print("Bulldog: entered kernel_main\n");
+    // Before any protected frame that returns captures the old canary.
+    kernel::stack::init_stack_guard();
+
     // 🎨 Framebuffer setup
     let framebuffer = boot_info.framebuffer.as_mut().expect("BootInfo.framebuffer must be present");
     let mut fb = KernelFramebuffer::from_bootloader(framebuffer);
@@ -77,6 +84,7 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
 
     // 🪵 Logging
     logger_init(LevelFilter::Info);
+    kernel::serial::mux_init();
     info!("Exited logger_init");
     info!("Framebuffer format: {:?}, size: {}x{}", fb.pixel_format, fb.width, fb.height);
 
@@ -91,6 +99,11 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
             .expect("BootInfo must provide physical memory offset")
     );
     let memory_regions: &'static [bootloader_api::info::MemoryRegion] = &boot_info.memory_regions;
+    kernel::memory::wx::record_kernel_image(
+        boot_info.kernel_addr,
+        boot_info.kernel_len,
+        boot_info.kernel_image_offset,
+    );
 
     match kernel_init(memory_regions, phys_mem_offset) {
         Ok(_) => info!("kernel_init completed successfully"),
@@ -99,44 +112,35 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
 
     info!("Returned to main");
 
+    // Self-test (scripts/panic-selftest.sh): the panic report must reach
+    // COM1 with the heap unusable.
+    #[cfg(feature = "panic-selftest")]
+    {
+        kernel::allocator::poison_heap();
+        panic!("panic-selftest: heap poisoned");
+    }
+
     hlt_loop();
 }
 
 /// Panic handler.
-/// Prints panic info over serial port, then halts in `hlt_loop`.
+/// Flushes the logger, then prints panic info over serial without allocating
+/// or locking (the panic may have come from the allocator or with a lock
+/// held), then halts with interrupts off.
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    unsafe {
-        serial_print("KERNEL PANIC: ");
-        if let Some(location) = info.location() {
-            serial_print(" at ");
-            serial_print(location.file());
-            serial_print(":");
-            serial_print(&location.line().to_string());
-        }
-        serial_print("\n");
-    }
-    hlt_loop();
-}
-
-/// Write a single byte to COM1 serial port (0x3F8).
-fn serial_write_byte(byte: u8) {
-    unsafe {
-        let mut port = Port::new(0x3F8);
-        port.write(byte);
-    }
-}
-
-/// Print a string to COM1 serial port.
-fn serial_print(s: &str) {
-    for byte in s.bytes() {
-        serial_write_byte(byte);
+    kernel::logger::flush_all();
+    emergency::print("KERNEL PANIC");
+    if let Some(location) = info.location() {
+        emergency::print(" at ");
+        emergency::print(location.file());
+        emergency::print(":");
+        emergency::print_dec(location.line() as u64);
+        emergency::print(":");
+        emergency::print_dec(location.column() as u64);
     }
+    emergency::print(": ");
+    let _ = write!(EmergencyWriter, "{}", info.message());
+    emergency::print("\n");
+    kernel::halt_forever();
 }
-
-
-
-
-
-
-
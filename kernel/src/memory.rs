@@ -2,7 +2,8 @@ use bootloader_api::info::{MemoryRegion, MemoryRegionKind};
 use x86_64::{
     PhysAddr, VirtAddr,
     structures::paging::{
-        FrameAllocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags,
+        mapper::{MapToError, UnmapError},
+        FrameAllocator, FrameDeallocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags,
         PhysFrame, Size4KiB,
     },
     registers::control::Cr3,
@@ -12,7 +13,36 @@ use log::{info, debug, warn, error, trace}; // log macros
 extern crate alloc;
 use alloc::vec::Vec;
 use alloc::collections::BTreeSet;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
 use crate::apic::LAPIC_VIRT_BASE;
+use crate::allocator::{fixed_size_block::HeapStats, ALLOCATOR, HEAP_SIZE};
+use buddy::BuddyAllocator;
+
+/// User/kernel address newtypes.
+pub mod addr;
+/// Buddy allocator backing `BootInfoFrameAllocator`.
+pub mod buddy;
+/// Per-process PML4 hierarchies.
+pub mod address_space;
+/// Per-process virtual memory area tracking.
+pub mod vma;
+/// Page-validated copies to and from user memory.
+pub mod usercopy;
+/// Late-boot W^X and page-flag audit.
+pub mod wx;
+
+/// Virtual offset at which the bootloader maps all physical memory.
+/// Recorded by `init_offset_page_table` so later code can reach frames directly.
+static PHYS_MEM_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Global frame allocator, installed by `kernel_init` before the initcalls run.
+/// Lets drivers (MMIO, DMA) and later subsystems allocate frames.
+pub static FRAME_ALLOCATOR: Mutex<Option<BootInfoFrameAllocator>> = Mutex::new(None);
+
+/// Upper bound (exclusive) of the DMA zone: frames below 4 GiB are
+/// reachable by devices limited to 32-bit physical addresses.
+pub const DMA_ZONE_LIMIT: u64 = 0x1_0000_0000;
 
 /// Same safety requirements as `init`.
 unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static mut PageTable {
@@ -25,9 +55,15 @@ unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static mut
 
 /// Initializes an OffsetPageTable using the given physical memory offset.
 pub unsafe fn init_offset_page_table(physical_memory_offset: VirtAddr) -> OffsetPageTable<'static> {
+    PHYS_MEM_OFFSET.store(physical_memory_offset.as_u64(), Ordering::Relaxed);
     OffsetPageTable::new(active_level_4_table(physical_memory_offset), physical_memory_offset)
 }
 
+/// Translate a physical address into its virtual alias in the physical memory mapping.
+pub fn phys_to_virt(phys: PhysAddr) -> VirtAddr {
+    VirtAddr::new(PHYS_MEM_OFFSET.load(Ordering::Relaxed) + phys.as_u64())
+}
+
 /// A FrameAllocator that always returns `None`.
 pub struct EmptyFrameAllocator;
 
@@ -45,34 +81,97 @@ pub struct PreHeapAllocator {
 
 pub struct BootInfoFrameAllocator {
     pub memory_map: &'static [MemoryRegion],
-    pub frames: Vec<PhysFrame>,
-    pub next: usize,
+    /// Usable frames managed, whether free or allocated.
+    pub total_frames: usize,
+    /// Free usable frames.
+    pub buddy: BuddyAllocator,
     pub allocated: FrameBitmap,
 }
 
+/// One bit per frame from physical address 0, set when the frame is in use.
 pub struct FrameBitmap {
-    bits: *mut [u8; 32768],
-    base_address: u64,     // e.g., 0x100000
-    frame_count: usize,    // e.g., 262_144
+    bits: *mut u8,
+    len: usize,            // bytes of `bits`
+    base_address: u64,     // first frame reported by `all_frames`, e.g. 0x100000
+    frame_count: usize,    // frames covered, from address 0
 }
 
+/// Fallback storage covering the first 1 GiB, used when the bitmap cannot
+/// be placed in allocated frames.
 static mut BITMAP: [u8; 32768] = [0; 32768];
 
+// Safety: the bitmap points at storage owned by the allocator and is only
+// reached through it (guarded by `FRAME_ALLOCATOR`).
+unsafe impl Send for FrameBitmap {}
+
 impl FrameBitmap {
+    /// Bitmap over the static array (first 1 GiB).
     pub fn new() -> Self {
-        unsafe {
-            FrameBitmap {
-                bits: &raw mut BITMAP,
-                base_address: 0x100000, // Start at 1 MiB
-                frame_count: 262_144,   // 1 GiB of 4 KiB frames
-            }
+        FrameBitmap {
+            bits: (&raw mut BITMAP).cast::<u8>(),
+            len: 32768,
+            base_address: 0x100000, // Start at 1 MiB
+            frame_count: 32768 * 8, // 1 GiB of 4 KiB frames
+        }
+    }
+
+    /// Bitmap covering every `Usable` frame in `memory_map`, stored in
+    /// frames taken from `buddy` (and marked used in itself).
+    ///
+    /// Falls back to `new` if the frames cannot be allocated. Coverage is
+    /// capped at what one maximum-order block can describe (128 GiB).
+    pub fn for_memory_map(memory_map: &[MemoryRegion], buddy: &mut BuddyAllocator) -> Self {
+        let top = memory_map
+            .iter()
+            .filter(|r| r.kind == MemoryRegionKind::Usable)
+            .map(|r| r.end)
+            .max()
+            .unwrap_or(0);
+
+        let max_bytes = (1usize << (buddy::MAX_ORDER - 1)) * 4096;
+        let mut bytes = ((top / 4096) as usize).div_ceil(8);
+        if bytes > max_bytes {
+            warn!("FrameBitmap: capping coverage at {} GiB of {:#x}", max_bytes * 8 / (1024 * 256), top);
+            bytes = max_bytes;
+        }
+        if bytes <= 32768 {
+            return Self::new();
+        }
+
+        let pages = bytes.div_ceil(4096);
+        let order = buddy::order_for(pages);
+        let Some(first) = buddy.allocate(order) else {
+            warn!("FrameBitmap: no block for {} bytes, tracking first 1 GiB only", bytes);
+            return Self::new();
+        };
+        for frame in PhysFrame::range(first, first + (1u64 << order)).skip(pages) {
+            buddy.free(frame, 0);
         }
+
+        let bits = phys_to_virt(first.start_address()).as_mut_ptr::<u8>();
+        unsafe { core::ptr::write_bytes(bits, 0, pages * 4096) };
+        let mut bitmap = FrameBitmap {
+            bits,
+            len: pages * 4096,
+            base_address: 0x100000,
+            frame_count: pages * 4096 * 8,
+        };
+        for frame in PhysFrame::range(first, first + pages as u64) {
+            bitmap.mark_used(frame);
+        }
+        info!("FrameBitmap: {} KiB covering {} MiB", pages * 4, bitmap.frame_count / 256);
+        bitmap
+    }
+
+    /// End (exclusive) of the physical range the bitmap covers.
+    pub fn end_address(&self) -> u64 {
+        self.frame_count as u64 * 4096
     }
 }
 
 impl FrameBitmap {
-    fn as_slice(&self) -> &[u8; 32768] {
-        unsafe { &*self.bits }
+    fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.bits, self.len) }
     }
 
   pub fn contains(&self, frame: PhysFrame) -> bool {
@@ -93,8 +192,8 @@ impl FrameBitmap {
 
 impl FrameBitmap {
     pub fn all_frames(&self) -> impl Iterator<Item = PhysFrame> {
-        (0..self.frame_count).map(move |i| {
-            let addr = self.base_address + (i as u64) * 4096;
+        (self.base_address / 4096..self.frame_count as u64).map(move |i| {
+            let addr = i * 4096;
             PhysFrame::containing_address(PhysAddr::new(addr))
         })
     }
@@ -134,8 +233,8 @@ impl FrameBitmap {
 
 impl FrameBitmap {
 
-fn as_mut_slice(&mut self) -> &mut [u8; 32768] {
-    unsafe { &mut *self.bits }
+fn as_mut_slice(&mut self) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(self.bits, self.len) }
 }
 
 
@@ -153,6 +252,17 @@ fn as_mut_slice(&mut self) -> &mut [u8; 32768] {
     true
 }
 
+    /// Clear a frame's bit when it is returned to the allocator.
+    pub fn mark_free(&mut self, frame: PhysFrame) {
+        let index = frame.start_address().as_u64() / 4096;
+        let byte = (index / 8) as usize;
+        let bit = (index % 8) as u8;
+
+        if let Some(b) = self.as_mut_slice().get_mut(byte) {
+            *b &= !(1 << bit);
+        }
+    }
+
 
 }
 
@@ -160,7 +270,12 @@ fn as_mut_slice(&mut self) -> &mut [u8; 32768] {
 
 impl PreHeapAllocator {
     pub fn into_vec(self) -> Vec<PhysFrame> {
-        self.frames.iter().filter_map(|&f| f).collect()
+        self.frames[self.next..].iter().filter_map(|&f| f).collect()
+    }
+
+    /// Frames handed out so far (heap pages and their page tables).
+    pub fn used_frames(&self) -> impl Iterator<Item = PhysFrame> + '_ {
+        self.frames[..self.next].iter().filter_map(|&f| f)
     }
 }
 
@@ -168,11 +283,11 @@ impl PreHeapAllocator {
 unsafe impl FrameAllocator<Size4KiB> for PreHeapAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame> {
         while self.next < self.frames.len() {
-            if let Some(frame) = self.frames[self.next].take() {
-                self.next += 1;
-                return Some(frame);
-            }
+            let frame = self.frames[self.next];
             self.next += 1;
+            if frame.is_some() {
+                return frame;
+            }
         }
         None
     }
@@ -180,15 +295,34 @@ unsafe impl FrameAllocator<Size4KiB> for PreHeapAllocator {
 
 
 impl BootInfoFrameAllocator {
-    pub fn new(memory_map: &'static [MemoryRegion], frames: Vec<PhysFrame>) -> Self {
+    /// Build the allocator from the `Usable` regions of the memory map,
+    /// leaving out `reserved` frames (already handed out before the heap).
+    pub fn new(memory_map: &'static [MemoryRegion], reserved: &[PhysFrame]) -> Self {
     info!("Entered BootInfoFrameAllocator::new");
 
+        let mut buddy = BuddyAllocator::new();
+        for region in memory_map.iter().filter(|r| r.kind == MemoryRegionKind::Usable) {
+            let start = region.start.max(0x10000); // skip low memory
+            if start < region.end {
+                buddy.add_region(start, region.end);
+            }
+        }
+
+        let total_frames = buddy.free_frames();
+        for &frame in reserved {
+            buddy.reserve(frame);
+        }
+        let mut allocated = FrameBitmap::for_memory_map(memory_map, &mut buddy);
+        for &frame in reserved {
+            allocated.mark_used(frame);
+        }
+        info!("Frame allocator: {} free frames", buddy.free_frames());
+
         BootInfoFrameAllocator {
             memory_map,
-            frames,
-            next: 0,
-            allocated: FrameBitmap::new(),
-
+            total_frames,
+            buddy,
+            allocated,
         }
     }
 }
@@ -203,13 +337,6 @@ impl BootInfoFrameAllocator {
 
 //-----------------------------START OF SECOND HALF-------------------------------
 
-impl BootInfoFrameAllocator {
-    /// Convert allocator into a vector of frames.
-    pub fn into_vec(self) -> Vec<PhysFrame> {
-        self.frames
-    }
-}
-
 impl BootInfoFrameAllocator {
     /// Check if a frame is already allocated.
     pub fn is_allocated(&self, frame: PhysFrame) -> bool {
@@ -288,7 +415,9 @@ impl BootInfoFrameAllocator {
                         region.start, region.end, region.kind
                     );
 
-                    for addr in (region.start..region.end).step_by(4096) {
+                    // Frames past the bitmap are never handed out.
+                    let end = region.end.min(self.allocated.end_address());
+                    for addr in (region.start..end).step_by(4096) {
                         let frame = PhysFrame::containing_address(PhysAddr::new(addr));
                         self.allocated.mark_used(frame);
                     }
@@ -302,41 +431,129 @@ impl BootInfoFrameAllocator {
     /// Full allocator — requires heap to be initialized.
     /// 
     /// # Safety
-    /// Must only be called once heap is ready.
+    /// Must only be called once heap is ready, and no usable frame may be
+    /// in use yet (use `new` with the pre-heap frames otherwise).
     pub unsafe fn init(memory_map: &'static [MemoryRegion]) -> Self {
         info!("Entered BootInfoFrameAllocator::init");
         debug!("memory_map.len = {}", memory_map.len());
+        Self::new(memory_map, &[])
+    }
+}
 
-        let mut frames = Vec::new();
+unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
+    /// Prefers the Normal zone, keeping DMA-capable frames for `alloc_dma`.
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        let frame = self.allocate_preferring_normal(0)?;
+        self.allocated.mark_used(frame); // track allocation
+        Some(frame)
+    }
+}
 
-        for region in memory_map.iter() {
-            for addr in (region.start..region.end).step_by(4096) {
-                let frame = PhysFrame::containing_address(PhysAddr::new(addr));
-                if frame.start_address().as_u64() < 0x10000 {
-                    continue; // skip low memory
-                }
-                frames.push(frame);
-            }
+impl FrameDeallocator<Size4KiB> for BootInfoFrameAllocator {
+    /// Return a frame from `allocate_frame`. Frames not marked allocated are
+    /// ignored with a warning rather than double-freed into the pool.
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
+        if !self.allocated.contains(frame) {
+            warn!("deallocate_frame: {:#x} is not allocated", frame.start_address().as_u64());
+            return;
         }
+        self.deallocate_order(frame, 0);
+    }
+}
 
-        BootInfoFrameAllocator {
-            memory_map,
-            frames,
-            next: 0,
-            allocated: FrameBitmap::new(),
+/// Return `frame` to the global frame allocator.
+///
+/// # Safety
+/// The frame must have come from `FRAME_ALLOCATOR` and nothing may use it afterwards.
+pub unsafe fn free_frame(frame: PhysFrame) {
+    match FRAME_ALLOCATOR.lock().as_mut() {
+        Some(allocator) => unsafe { allocator.deallocate_frame(frame) },
+        None => warn!("free_frame: no frame allocator, leaking {:#x}", frame.start_address().as_u64()),
+    }
+}
+
+/// Frames kept aside for the page-fault path: enough for one demand-paged
+/// page plus the three page tables above it, twice over.
+pub const FAULT_RESERVE_FRAMES: usize = 8;
+
+/// Ticks between top-ups of the fault reserve.
+const FAULT_RESERVE_PERIOD: u64 = 10;
+
+/// Free frames the page-fault handler allocates from. It must neither wait
+/// on `FRAME_ALLOCATOR` nor touch the heap, where the buddy allocator keeps
+/// its free sets; `refill_fault_reserve` tops this up from normal context.
+static FAULT_RESERVE: Mutex<FrameReserve> = Mutex::new(FrameReserve::new());
+
+/// Fixed-size stack of free frames; allocating and freeing never touch the heap.
+#[derive(Debug)]
+pub struct FrameReserve {
+    frames: [Option<PhysFrame>; FAULT_RESERVE_FRAMES],
+    len: usize,
+}
+
+impl FrameReserve {
+    const fn new() -> Self {
+        FrameReserve { frames: [None; FAULT_RESERVE_FRAMES], len: 0 }
+    }
+
+    /// Frames currently held.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, frame: PhysFrame) -> Result<(), PhysFrame> {
+        if self.len == FAULT_RESERVE_FRAMES {
+            return Err(frame);
         }
+        self.frames[self.len] = Some(frame);
+        self.len += 1;
+        Ok(())
     }
 }
 
-unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
+unsafe impl FrameAllocator<Size4KiB> for FrameReserve {
     fn allocate_frame(&mut self) -> Option<PhysFrame> {
-        if self.next >= self.frames.len() {
+        if self.len == 0 {
             return None;
         }
-        let frame = self.frames[self.next];
-        self.next += 1;
-        self.allocated.mark_used(frame); // track allocation
-        Some(frame)
+        self.len -= 1;
+        self.frames[self.len].take()
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for FrameReserve {
+    /// Put back a frame taken from the reserve (e.g. after a failed mapping).
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
+        if let Err(frame) = self.push(frame) {
+            warn!("fault reserve: full, leaking {:#x}", frame.start_address().as_u64());
+        }
+    }
+}
+
+/// The fault reserve, unless someone holds it. Never blocks.
+pub fn try_fault_reserve() -> Option<spin::MutexGuard<'static, FrameReserve>> {
+    FAULT_RESERVE.try_lock()
+}
+
+/// Top the fault reserve up from `FRAME_ALLOCATOR`. Normal context only.
+pub fn refill_fault_reserve() {
+    // Interrupts off: a fault taken meanwhile would find the reserve locked.
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut reserve = FAULT_RESERVE.lock();
+        let mut allocator = FRAME_ALLOCATOR.lock();
+        let Some(allocator) = allocator.as_mut() else { return };
+        while reserve.len() < FAULT_RESERVE_FRAMES {
+            let Some(frame) = allocator.allocate_frame() else { break };
+            let _ = reserve.push(frame);
+        }
+    });
+}
+
+/// Fill the fault reserve and keep it topped up from the idle loop.
+pub fn start_fault_reserve() {
+    refill_fault_reserve();
+    if let Err(e) = crate::time::interval("fault-reserve", FAULT_RESERVE_PERIOD, refill_fault_reserve) {
+        error!("fault reserve refill not scheduled: {:?}", e);
     }
 }
 
@@ -386,6 +603,133 @@ pub fn map_page(
     }
 }
 
+/// Errors returned by `remap_with_flags`.
+#[derive(Debug)]
+pub enum RemapError {
+    /// The page has no mapping to change.
+    NotMapped,
+    /// Re-creating the mapping failed.
+    Map(MapToError<Size4KiB>),
+}
+
+/// Unmap the page containing `virt` and flush its TLB entry.
+///
+/// Returns the frame it mapped; the caller decides whether to free it.
+pub fn unmap_page(mapper: &mut impl Mapper<Size4KiB>, virt: VirtAddr) -> Result<PhysFrame, UnmapError> {
+    let page = Page::<Size4KiB>::containing_address(virt);
+    let (frame, flush) = mapper.unmap(page)?;
+    flush.flush();
+    Ok(frame)
+}
+
+/// Replace the flags of the page containing `virt`, keeping its frame.
+///
+/// Unmaps and maps again so missing bits (e.g. `WRITABLE`) are also added
+/// to the parent tables. The page is briefly unmapped: do not use this on
+/// the code or stack currently running.
+pub fn remap_with_flags(
+    mapper: &mut impl Mapper<Size4KiB>,
+    virt: VirtAddr,
+    flags: PageTableFlags,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<PhysFrame, RemapError> {
+    let page = Page::<Size4KiB>::containing_address(virt);
+    let frame = unmap_page(mapper, virt).map_err(|_| RemapError::NotMapped)?;
+    unsafe {
+        mapper
+            .map_to(page, frame, flags, frame_allocator)
+            .map_err(RemapError::Map)?
+            .flush();
+    }
+    Ok(frame)
+}
+
+/// Apply `flags` to every mapped page in `range` with `remap_with_flags`.
+///
+/// Unmapped pages are logged and skipped. Returns the number of pages changed.
+pub fn protect_range(
+    mapper: &mut impl Mapper<Size4KiB>,
+    range: core::ops::Range<VirtAddr>,
+    flags: PageTableFlags,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<usize, MapToError<Size4KiB>> {
+    let pages = Page::<Size4KiB>::range(
+        Page::containing_address(range.start),
+        Page::containing_address(range.end - 1u64) + 1,
+    );
+    let mut changed = 0;
+    for page in pages {
+        match remap_with_flags(mapper, page.start_address(), flags, frame_allocator) {
+            Ok(_) => changed += 1,
+            Err(RemapError::NotMapped) => {
+                error!("protect_range: page {:#x} not mapped", page.start_address().as_u64())
+            }
+            Err(RemapError::Map(e)) => return Err(e),
+        }
+    }
+    Ok(changed)
+}
+
+/// Pages printed by `dump_mapping` before it stops.
+const DUMP_MAX_PAGES: usize = 64;
+
+/// Walk the active page tables over `range` and log every level's entry.
+///
+/// Each mapped 4 KiB page (or huge page) gets one line per level with the
+/// table index, the entry's target address and its flags. Unmapped spans
+/// are reported once per missing entry and skipped. Stops after
+/// `DUMP_MAX_PAGES` pages.
+pub fn dump_mapping(range: core::ops::Range<VirtAddr>) {
+    let (pml4, cr3_flags) = Cr3::read();
+    info!(
+        "dump_mapping: {:#x}..{:#x} (CR3={:#x} {:?})",
+        range.start.as_u64(),
+        range.end.as_u64(),
+        pml4.start_address().as_u64(),
+        cr3_flags
+    );
+
+    let mut addr = range.start.align_down(4096u64).as_u64();
+    let mut pages = 0;
+    while addr < range.end.as_u64() && pages < DUMP_MAX_PAGES {
+        pages += 1;
+        let virt = VirtAddr::new_truncate(addr);
+        let indices = [virt.p4_index(), virt.p3_index(), virt.p2_index(), virt.p1_index()];
+        let mut table_phys = pml4.start_address();
+        info!("  {:#x}:", virt.as_u64());
+
+        for (depth, index) in indices.iter().enumerate() {
+            let level = 4 - depth;
+            let table = unsafe { &*phys_to_virt(table_phys).as_ptr::<PageTable>() };
+            let entry = &table[*index];
+            let flags = entry.flags();
+            // Bytes covered by one entry at this level.
+            let span = 1u64 << (12 + 9 * (level - 1));
+
+            if !flags.contains(PageTableFlags::PRESENT) {
+                info!("    P{}[{:>3}] not present", level, u16::from(*index));
+                addr = (addr & !(span - 1)) + span;
+                break;
+            }
+            info!(
+                "    P{}[{:>3}] -> {:#x} {:?}",
+                level,
+                u16::from(*index),
+                entry.addr().as_u64(),
+                flags
+            );
+            if level == 1 || flags.contains(PageTableFlags::HUGE_PAGE) {
+                addr = (addr & !(span - 1)) + span;
+                break;
+            }
+            table_phys = entry.addr();
+        }
+    }
+    if addr < range.end.as_u64() {
+        info!("  ... stopped after {} pages at {:#x}", DUMP_MAX_PAGES, addr);
+    }
+}
+
 /// Find the first unused frame in the allocator bitmap.
 pub fn find_unused_frame(allocator: &FrameBitmap) -> Option<PhysFrame> {
     for frame in allocator.all_frames() {
@@ -396,7 +740,262 @@ pub fn find_unused_frame(allocator: &FrameBitmap) -> Option<PhysFrame> {
     None
 }
 
+/// Physical memory zones understood by the frame allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryZone {
+    /// Below `DMA_ZONE_LIMIT`; usable by 32-bit DMA engines.
+    Dma,
+    /// Everything above the DMA zone.
+    Normal,
+}
+
+impl MemoryZone {
+    /// Return the zone a frame belongs to.
+    pub fn of(frame: PhysFrame) -> Self {
+        if frame.start_address().as_u64() < DMA_ZONE_LIMIT {
+            MemoryZone::Dma
+        } else {
+            MemoryZone::Normal
+        }
+    }
+}
+
+/// A physically contiguous, DMA-capable buffer.
+/// `virt` is the alias of `phys` inside the physical memory mapping.
+#[derive(Debug, Clone, Copy)]
+pub struct DmaRegion {
+    pub phys: PhysAddr,
+    pub virt: VirtAddr,
+    pub len: usize,
+}
+
+impl BootInfoFrameAllocator {
+    /// Allocate `2^order` physically contiguous frames, aligned to their size.
+    pub fn allocate_order(&mut self, order: usize) -> Option<PhysFrame> {
+        let first = self.allocate_preferring_normal(order)?;
+        for frame in PhysFrame::range(first, first + (1u64 << order)) {
+            self.allocated.mark_used(frame);
+        }
+        Some(first)
+    }
+
+    /// Return a block obtained from `allocate_order` (order 0 for `allocate_frame`).
+    pub fn deallocate_order(&mut self, first: PhysFrame, order: usize) {
+        for frame in PhysFrame::range(first, first + (1u64 << order)) {
+            self.allocated.mark_free(frame);
+        }
+        self.buddy.free(first, order);
+    }
+
+    /// Take a `2^order` block from the Normal zone, falling back to the DMA
+    /// zone only once Normal is exhausted. Does not mark it used.
+    fn allocate_preferring_normal(&mut self, order: usize) -> Option<PhysFrame> {
+        self.buddy
+            .allocate_in(order, DMA_ZONE_LIMIT, u64::MAX)
+            .or_else(|| self.buddy.allocate_in(order, 0, DMA_ZONE_LIMIT))
+    }
+
+    /// Allocate a single frame from the requested zone.
+    pub fn allocate_frame_in_zone(&mut self, zone: MemoryZone) -> Option<PhysFrame> {
+        let frame = match zone {
+            MemoryZone::Dma => self.buddy.allocate_in(0, 0, DMA_ZONE_LIMIT),
+            MemoryZone::Normal => self.buddy.allocate_in(0, DMA_ZONE_LIMIT, u64::MAX),
+        }?;
+        self.allocated.mark_used(frame);
+        Some(frame)
+    }
+
+    /// Allocate `count` physically contiguous frames below `DMA_ZONE_LIMIT`,
+    /// starting on an `align`-byte boundary.
+    ///
+    /// Takes the smallest buddy block covering both `count` and `align` and
+    /// frees the unused tail. Returns the first frame of the run.
+    pub fn allocate_contiguous_dma(&mut self, count: usize, align: u64) -> Option<PhysFrame> {
+        if count == 0 {
+            return None;
+        }
+
+        let order = buddy::order_for(count).max(buddy::order_for((align / 4096) as usize));
+        let first = self.buddy.allocate_in(order, 0, DMA_ZONE_LIMIT)?;
+        for frame in PhysFrame::range(first, first + (1u64 << order)).skip(count) {
+            self.buddy.free(frame, 0);
+        }
+        for frame in PhysFrame::range(first, first + count as u64) {
+            self.allocated.mark_used(frame);
+        }
+        Some(first)
+    }
+}
+
+/// Allocate a zeroed, physically contiguous DMA buffer of at least `len` bytes.
+///
+/// - `align` must be a power of two; it is raised to at least 4 KiB.
+/// - Memory comes from the DMA zone (below 4 GiB).
+/// - Returns both the physical address (for the device) and the virtual alias (for the driver).
+///
+/// Requires the global `FRAME_ALLOCATOR` to be installed by `kernel_init`.
+pub fn alloc_dma(len: usize, align: usize) -> Option<DmaRegion> {
+    assert!(align.is_power_of_two());
+
+    let count = (len + 4095) / 4096;
+    let align = (align as u64).max(4096);
+
+    let frame = FRAME_ALLOCATOR
+        .lock()
+        .as_mut()?
+        .allocate_contiguous_dma(count, align);
+
+    let frame = match frame {
+        Some(frame) => frame,
+        None => {
+            warn!("alloc_dma: no contiguous run of {} frames (align {:#x})", count, align);
+            return None;
+        }
+    };
 
+    let phys = frame.start_address();
+    let virt = phys_to_virt(phys);
+    unsafe {
+        core::ptr::write_bytes(virt.as_mut_ptr::<u8>(), 0, count * 4096);
+    }
 
+    debug!("alloc_dma: {} bytes at phys={:#x}, virt={:#x}", count * 4096, phys.as_u64(), virt.as_u64());
+    Some(DmaRegion { phys, virt, len: count * 4096 })
+}
 
+/// Return a buffer obtained from `alloc_dma` to the frame allocator.
+///
+/// # Safety
+/// Neither the device nor the driver may touch the buffer afterwards.
+pub unsafe fn free_dma(region: DmaRegion) {
+    let first = PhysFrame::containing_address(region.phys);
+    let frames = PhysFrame::range(first, first + (region.len / 4096) as u64);
+
+    match FRAME_ALLOCATOR.lock().as_mut() {
+        Some(allocator) => {
+            for frame in frames {
+                unsafe { allocator.deallocate_frame(frame) };
+            }
+        }
+        None => warn!("free_dma: no frame allocator, leaking {:#x}", region.phys.as_u64()),
+    }
+    debug!("free_dma: {} bytes at phys={:#x}", region.len, region.phys.as_u64());
+}
 
+/// Start of the virtual window used for runtime MMIO mappings (BARs, framebuffers).
+pub const MMIO_VIRT_BASE: u64 = 0xFFFF_FE00_0000_0000;
+
+/// Size of the MMIO window (one level-4 entry).
+pub const MMIO_VIRT_SIZE: u64 = 512 * 1024 * 1024 * 1024;
+
+/// Next free address in the MMIO window (bump allocated; only the most
+/// recent reservation can be returned, by a failed `map_mmio`).
+static MMIO_NEXT: AtomicU64 = AtomicU64::new(MMIO_VIRT_BASE);
+
+/// Map `len` bytes of device memory at `phys` into the MMIO window.
+///
+/// `flags` are added to `PRESENT | WRITABLE | NO_EXECUTE` (e.g. `NO_CACHE`
+/// for registers, `WRITE_THROUGH` for framebuffers). Returns the virtual
+/// address corresponding to `phys`.
+///
+/// Requires the global `FRAME_ALLOCATOR` (for page-table frames).
+pub fn map_mmio(phys: PhysAddr, len: u64, flags: PageTableFlags) -> Option<VirtAddr> {
+    let offset = PHYS_MEM_OFFSET.load(Ordering::Relaxed);
+    let mut allocator = FRAME_ALLOCATOR.lock();
+    let allocator = allocator.as_mut()?;
+
+    let page_offset = phys.as_u64() & 0xFFF;
+    let pages = (page_offset + len + 4095) / 4096;
+    let size = pages * 4096;
+
+    // Reserve window space only if it fits, so failed calls consume none.
+    let reserved = MMIO_NEXT.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+        next.checked_add(size).filter(|&end| end <= MMIO_VIRT_BASE + MMIO_VIRT_SIZE)
+    });
+    let Ok(base) = reserved else {
+        error!("map_mmio: window exhausted mapping {:#x} bytes at {:#x}", len, phys.as_u64());
+        return None;
+    };
+
+    let mut mapper = unsafe { init_offset_page_table(VirtAddr::new(offset)) };
+    let flags = flags | PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+    let first = PhysFrame::<Size4KiB>::containing_address(phys);
+    let page_at = |i: u64| Page::<Size4KiB>::containing_address(VirtAddr::new(base + i * 4096));
+
+    for i in 0..pages {
+        let frame = first + i;
+        match unsafe { mapper.map_to(page_at(i), frame, flags, allocator) } {
+            Ok(flush) => flush.flush(),
+            Err(e) => {
+                error!("map_mmio: mapping {:#x} failed: {:?}", frame.start_address().as_u64(), e);
+                // Undo the partial mapping and give the space back if no
+                // later reservation was made on top of it.
+                for mapped in 0..i {
+                    if let Ok((_, flush)) = mapper.unmap(page_at(mapped)) {
+                        flush.flush();
+                    }
+                }
+                let _ = MMIO_NEXT.compare_exchange(base + size, base, Ordering::Relaxed, Ordering::Relaxed);
+                return None;
+            }
+        }
+    }
+
+    debug!("map_mmio: phys={:#x} len={:#x} -> virt={:#x}", phys.as_u64(), len, base + page_offset);
+    Some(VirtAddr::new(base + page_offset))
+}
+
+/// Snapshot of physical and heap memory use, from `stats`.
+#[derive(Debug, Clone, Copy)]
+pub struct MemStats {
+    /// Usable frames managed by `FRAME_ALLOCATOR` (0 before it is installed).
+    pub total_frames: usize,
+    pub free_frames: usize,
+    /// Heap counters, including per-size-class free-list lengths.
+    pub heap: HeapStats,
+}
+
+impl MemStats {
+    pub fn used_frames(&self) -> usize {
+        self.total_frames - self.free_frames
+    }
+}
+
+fn collect_stats(frames: Option<&BootInfoFrameAllocator>, heap: HeapStats) -> MemStats {
+    MemStats {
+        total_frames: frames.map_or(0, |f| f.total_frames),
+        free_frames: frames.map_or(0, |f| f.buddy.free_frames()),
+        heap,
+    }
+}
+
+/// Current memory statistics.
+pub fn stats() -> MemStats {
+    let heap = ALLOCATOR.lock().stats();
+    collect_stats(FRAME_ALLOCATOR.lock().as_ref(), heap)
+}
+
+/// `stats`, or `None` if an allocator lock is held (e.g. when called while
+/// handling an allocation failure).
+pub fn try_stats() -> Option<MemStats> {
+    let heap = ALLOCATOR.try_lock()?.stats();
+    Some(collect_stats(FRAME_ALLOCATOR.try_lock()?.as_ref(), heap))
+}
+
+/// Log a `/proc/meminfo`-style summary of `stats`.
+pub fn log_meminfo(stats: &MemStats) {
+    info!(
+        "meminfo: frames {} total, {} used, {} free ({} KiB free)",
+        stats.total_frames,
+        stats.used_frames(),
+        stats.free_frames,
+        stats.free_frames * 4
+    );
+    info!(
+        "meminfo: heap {} bytes used, {} bytes free of {}",
+        stats.heap.fallback_used, stats.heap.fallback_free, HEAP_SIZE
+    );
+    for (size, _, free_blocks) in stats.heap.classes.iter().filter(|c| c.2 > 0) {
+        info!("meminfo: {:>5}-byte class: {} free block(s)", size, free_blocks);
+    }
+}
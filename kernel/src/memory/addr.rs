@@ -0,0 +1,141 @@
+//! Address types with provenance (`memory/addr.rs`).
+//!
+//! - `UserVirtAddr`: a virtual address inside the user window
+//!   (`USER_SPACE_START..USER_SPACE_END`). Anything that came from a
+//!   process (fault addresses, `brk` arguments, future syscall pointers)
+//!   is checked into this type before use.
+//! - `KernelVirtAddr`: a canonical virtual address outside the user window,
+//!   e.g. the physical-memory alias returned by `KernelVirtAddr::from_phys`.
+//! - Physical addresses stay `x86_64::PhysAddr`.
+//!
+//! Constructors validate; there is no `From<u64>`, so an unchecked integer
+//! cannot silently become a user or kernel pointer.
+
+use core::fmt;
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::phys_to_virt;
+
+/// Start of the user window: level-4 slot 1 (slot 0 is left to the bootloader's
+/// low mappings).
+pub const USER_SPACE_START: u64 = 0x0000_0080_0000_0000;
+
+/// End (exclusive) of the user window: level-4 slots 1..128 (64 TiB). The
+/// kernel heap and other kernel mappings live above it.
+pub const USER_SPACE_END: u64 = 0x0000_4000_0000_0000;
+
+/// Why an address was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddrError {
+    /// Not a canonical x86_64 address.
+    NonCanonical,
+    /// Expected a user address, got one outside the user window.
+    NotUser,
+    /// Expected a kernel address, got one inside the user window.
+    NotKernel,
+    /// `addr + len` overflows or leaves the window.
+    RangeOverflow,
+}
+
+fn in_user_window(addr: u64) -> bool {
+    (USER_SPACE_START..USER_SPACE_END).contains(&addr)
+}
+
+/// A virtual address known to lie in the user window.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UserVirtAddr(VirtAddr);
+
+impl UserVirtAddr {
+    pub fn new(addr: u64) -> Result<Self, AddrError> {
+        if !in_user_window(addr) {
+            return Err(AddrError::NotUser);
+        }
+        Ok(UserVirtAddr(VirtAddr::new(addr)))
+    }
+
+    /// Check that `[self, self + len)` stays inside the user window and
+    /// return its end.
+    pub fn range_end(self, len: u64) -> Result<Self, AddrError> {
+        let end = self.0.as_u64().checked_add(len).ok_or(AddrError::RangeOverflow)?;
+        if end > USER_SPACE_END {
+            return Err(AddrError::RangeOverflow);
+        }
+        Ok(UserVirtAddr(VirtAddr::new(end)))
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0.as_u64()
+    }
+
+    /// The address as a plain `VirtAddr`, for page-table APIs.
+    pub fn as_virt(self) -> VirtAddr {
+        self.0
+    }
+}
+
+impl TryFrom<VirtAddr> for UserVirtAddr {
+    type Error = AddrError;
+    fn try_from(addr: VirtAddr) -> Result<Self, AddrError> {
+        UserVirtAddr::new(addr.as_u64())
+    }
+}
+
+impl fmt::Debug for UserVirtAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UserVirtAddr({:#x})", self.0.as_u64())
+    }
+}
+
+/// A canonical virtual address outside the user window.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KernelVirtAddr(VirtAddr);
+
+impl KernelVirtAddr {
+    pub fn new(addr: u64) -> Result<Self, AddrError> {
+        let virt = VirtAddr::try_new(addr).map_err(|_| AddrError::NonCanonical)?;
+        if in_user_window(addr) {
+            return Err(AddrError::NotKernel);
+        }
+        Ok(KernelVirtAddr(virt))
+    }
+
+    /// Address of a kernel object.
+    pub fn from_ptr<T>(ptr: *const T) -> Self {
+        // Kernel objects never live in the user window.
+        KernelVirtAddr(VirtAddr::from_ptr(ptr))
+    }
+
+    /// The alias of `phys` in the physical-memory mapping.
+    pub fn from_phys(phys: PhysAddr) -> Self {
+        KernelVirtAddr(phys_to_virt(phys))
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0.as_u64()
+    }
+
+    pub fn as_virt(self) -> VirtAddr {
+        self.0
+    }
+
+    pub fn as_ptr<T>(self) -> *const T {
+        self.0.as_ptr()
+    }
+
+    pub fn as_mut_ptr<T>(self) -> *mut T {
+        self.0.as_mut_ptr()
+    }
+}
+
+impl TryFrom<VirtAddr> for KernelVirtAddr {
+    type Error = AddrError;
+    fn try_from(addr: VirtAddr) -> Result<Self, AddrError> {
+        KernelVirtAddr::new(addr.as_u64())
+    }
+}
+
+impl fmt::Debug for KernelVirtAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KernelVirtAddr({:#x})", self.0.as_u64())
+    }
+}
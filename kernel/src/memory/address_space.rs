@@ -0,0 +1,257 @@
+//! Per-process address spaces (`memory/address_space.rs`).
+//!
+//! - `AddressSpace::new` builds a fresh PML4 that shares every top-level
+//!   entry of the kernel's table (kernel half) and leaves the rest empty
+//!   for the process (user half).
+//! - `map_user`/`unmap_user` manage private user pages. The address space
+//!   owns every frame reachable from its user slots (user pages and page
+//!   tables) plus the PML4; ownership is read off the tables, so no list of
+//!   frames is kept. Unmapped user pages and, on drop, all owned frames go
+//!   back to `FRAME_ALLOCATOR`.
+//! - `map_user_reserved` is the page-fault variant: frames come from the
+//!   fault reserve, it never blocks and never touches the heap.
+//! - `copy_from_user`/`copy_to_user` move bytes through this address
+//!   space's tables (see `usercopy`), whether or not it is active.
+//! - `activate` loads the PML4 into CR3, as a context switch will.
+//!
+//! The kernel is not confined to the upper canonical half here (the heap
+//! lives at `0x4444_4444_0000`), so "kernel half" means the top-level slots
+//! in use when the address space was created. Kernel mappings added later in
+//! a new top-level slot are not propagated.
+
+use log::debug;
+use x86_64::{
+    registers::control::{Cr3, Cr3Flags},
+    structures::paging::{
+        mapper::MapToError, FrameAllocator, FrameDeallocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, PhysFrame,
+        Size4KiB, Translate,
+    },
+    PhysAddr, VirtAddr,
+};
+
+use super::addr::UserVirtAddr;
+use super::usercopy::{self, UserCopyError};
+use super::{phys_to_virt, try_fault_reserve, FRAME_ALLOCATOR, PHYS_MEM_OFFSET};
+use core::sync::atomic::Ordering;
+
+/// Errors returned by `AddressSpace` operations.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AddressSpaceError {
+    /// `FRAME_ALLOCATOR` has not been installed yet.
+    NoFrameAllocator,
+    /// No free physical frames.
+    OutOfFrames,
+    /// The page falls in a top-level slot shared with the kernel.
+    KernelRegion,
+    /// The page is already mapped.
+    AlreadyMapped,
+    /// The page is not mapped.
+    NotMapped,
+    /// A parent entry maps a huge page over the page.
+    ParentHugePage,
+    /// The fault reserve is locked (the fault hit code refilling it).
+    Busy,
+}
+
+impl From<MapToError<Size4KiB>> for AddressSpaceError {
+    fn from(err: MapToError<Size4KiB>) -> Self {
+        match err {
+            MapToError::FrameAllocationFailed => AddressSpaceError::OutOfFrames,
+            MapToError::ParentEntryHugePage => AddressSpaceError::ParentHugePage,
+            MapToError::PageAlreadyMapped(_) => AddressSpaceError::AlreadyMapped,
+        }
+    }
+}
+
+/// A PML4 hierarchy owned by one process.
+#[derive(Debug)]
+pub struct AddressSpace {
+    pml4: PhysFrame,
+    /// Top-level slots inherited from the kernel table.
+    shared: [bool; 512],
+}
+
+fn table_at(frame: PhysFrame) -> &'static mut PageTable {
+    unsafe { &mut *phys_to_virt(frame.start_address()).as_mut_ptr::<PageTable>() }
+}
+
+impl AddressSpace {
+    /// Create an address space sharing the current kernel mappings.
+    pub fn new() -> Result<Self, AddressSpaceError> {
+        let mut allocator = FRAME_ALLOCATOR.lock();
+        let allocator = allocator.as_mut().ok_or(AddressSpaceError::NoFrameAllocator)?;
+        let pml4 = allocator.allocate_frame().ok_or(AddressSpaceError::OutOfFrames)?;
+
+        let (kernel_pml4, _) = Cr3::read();
+        let kernel = table_at(kernel_pml4);
+        let table = table_at(pml4);
+        table.zero();
+
+        let mut shared = [false; 512];
+        for (i, entry) in kernel.iter().enumerate() {
+            if !entry.is_unused() {
+                table[i] = entry.clone();
+                shared[i] = true;
+            }
+        }
+
+        debug!("address space: new PML4 at {:#x}", pml4.start_address().as_u64());
+        Ok(AddressSpace { pml4, shared })
+    }
+
+    /// Physical frame of the PML4 (the value loaded into CR3).
+    pub fn pml4(&self) -> PhysFrame {
+        self.pml4
+    }
+
+    /// Number of frames owned by this address space.
+    pub fn frame_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_owned(|_| count += 1);
+        count
+    }
+
+    /// Visit every owned frame: user pages and user-slot page tables (each
+    /// table after its entries), then the PML4.
+    fn for_each_owned(&self, mut f: impl FnMut(PhysFrame)) {
+        fn walk(table: PhysFrame, level: u8, f: &mut impl FnMut(PhysFrame)) {
+            for entry in table_at(table).iter() {
+                // `map_user` only creates 4 KiB pages; `frame()` skips holes.
+                let Ok(frame) = entry.frame() else { continue };
+                if level == 1 {
+                    f(frame);
+                } else {
+                    walk(frame, level - 1, f);
+                }
+            }
+            f(table);
+        }
+
+        for (i, entry) in table_at(self.pml4).iter().enumerate() {
+            if self.shared[i] {
+                continue;
+            }
+            if let Ok(frame) = entry.frame() {
+                walk(frame, 3, &mut f);
+            }
+        }
+        f(self.pml4);
+    }
+
+    fn mapper(&self) -> OffsetPageTable<'static> {
+        let offset = VirtAddr::new(PHYS_MEM_OFFSET.load(Ordering::Relaxed));
+        unsafe { OffsetPageTable::new(table_at(self.pml4), offset) }
+    }
+
+    fn check_user(&self, page: Page) -> Result<(), AddressSpaceError> {
+        if self.shared[usize::from(page.p4_index())] {
+            Err(AddressSpaceError::KernelRegion)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Map a fresh zeroed frame at `page` with `flags` (plus `PRESENT | USER_ACCESSIBLE`).
+    pub fn map_user(&mut self, page: Page, flags: PageTableFlags) -> Result<PhysFrame, AddressSpaceError> {
+        self.check_user(page)?;
+        let mut allocator = FRAME_ALLOCATOR.lock();
+        let allocator = allocator.as_mut().ok_or(AddressSpaceError::NoFrameAllocator)?;
+        self.map_with(page, flags, allocator)
+    }
+
+    /// `map_user` for the page-fault path: takes frames from the fault
+    /// reserve with `try_lock`, so it never blocks and never touches the heap.
+    pub fn map_user_reserved(&mut self, page: Page, flags: PageTableFlags) -> Result<PhysFrame, AddressSpaceError> {
+        self.check_user(page)?;
+        let mut reserve = try_fault_reserve().ok_or(AddressSpaceError::Busy)?;
+        self.map_with(page, flags, &mut *reserve)
+    }
+
+    fn map_with<A>(&mut self, page: Page, flags: PageTableFlags, allocator: &mut A) -> Result<PhysFrame, AddressSpaceError>
+    where
+        A: FrameAllocator<Size4KiB> + FrameDeallocator<Size4KiB>,
+    {
+        let frame = allocator.allocate_frame().ok_or(AddressSpaceError::OutOfFrames)?;
+        unsafe {
+            core::ptr::write_bytes(phys_to_virt(frame.start_address()).as_mut_ptr::<u8>(), 0, 4096);
+        }
+
+        // Page tables created on the way stay in the hierarchy (and owned)
+        // even if the final mapping fails; the user frame goes back.
+        let flags = flags | PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        match unsafe { self.mapper().map_to(page, frame, flags, allocator) } {
+            Ok(flush) => {
+                // Inactive tables need no flush: loading CR3 flushes non-global entries.
+                if Cr3::read().0 == self.pml4 {
+                    flush.flush();
+                } else {
+                    flush.ignore();
+                }
+                Ok(frame)
+            }
+            Err(err) => {
+                unsafe { allocator.deallocate_frame(frame) };
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Remove the user mapping at `page` and free the frame it mapped.
+    ///
+    /// Returns the (now free) frame. Page-table frames stay owned until drop.
+    pub fn unmap_user(&mut self, page: Page) -> Result<PhysFrame, AddressSpaceError> {
+        self.check_user(page)?;
+        let mut mapper = self.mapper();
+        let (frame, flush) = mapper.unmap(page).map_err(|_| AddressSpaceError::NotMapped)?;
+        if Cr3::read().0 == self.pml4 {
+            flush.flush();
+        } else {
+            flush.ignore();
+        }
+
+        // Every user-slot mapping came from `map_user`, so the frame is ours.
+        unsafe { super::free_frame(frame) };
+        Ok(frame)
+    }
+
+    /// Translate a virtual address through this address space.
+    pub fn translate(&self, addr: VirtAddr) -> Option<PhysAddr> {
+        self.mapper().translate_addr(addr)
+    }
+
+    /// Copy `dst.len()` bytes from this address space at `src`.
+    pub fn copy_from_user(&self, dst: &mut [u8], src: UserVirtAddr) -> Result<(), UserCopyError> {
+        usercopy::copy_from_user(&self.mapper(), dst, src)
+    }
+
+    /// Copy `src` into this address space at `dst`.
+    pub fn copy_to_user(&self, dst: UserVirtAddr, src: &[u8]) -> Result<(), UserCopyError> {
+        usercopy::copy_to_user(&self.mapper(), dst, src)
+    }
+
+    /// Switch the CPU to this address space.
+    ///
+    /// # Safety
+    /// The address space must outlive its time in CR3, and the code and
+    /// stack currently in use must be mapped in it (kernel slots are shared).
+    pub unsafe fn activate(&self) {
+        unsafe { Cr3::write(self.pml4, Cr3Flags::empty()) };
+    }
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        assert!(Cr3::read().0 != self.pml4, "dropping the active address space");
+        let mut allocator = FRAME_ALLOCATOR.lock();
+        let Some(allocator) = allocator.as_mut() else {
+            return;
+        };
+        let mut freed = 0;
+        // Tables are freed only after their entries have been read.
+        self.for_each_owned(|frame| {
+            unsafe { allocator.deallocate_frame(frame) };
+            freed += 1;
+        });
+        debug!("address space: freed {} frames of PML4 {:#x}", freed, self.pml4.start_address().as_u64());
+    }
+}
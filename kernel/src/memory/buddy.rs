@@ -0,0 +1,142 @@
+//! Buddy allocator for physical frames (`memory/buddy.rs`).
+//!
+//! - Free memory is kept as naturally aligned blocks of `2^order` frames,
+//!   one ordered free set per order.
+//! - Allocation takes the lowest suitable block, splitting larger ones;
+//!   freeing merges a block with its buddy while the buddy is free.
+//! - `allocate_in` restricts the search to a physical range, which is how
+//!   zone (DMA/normal) requests are served.
+//!
+//! Blocks are stored by frame number in `BTreeSet`s, so allocation and free
+//! are `O(log n)` per order and the allocator needs the heap.
+
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use x86_64::{structures::paging::PhysFrame, PhysAddr};
+
+/// Number of orders; the largest block is `2^(MAX_ORDER - 1)` frames (4 MiB).
+pub const MAX_ORDER: usize = 11;
+
+/// Physical frames managed as power-of-two blocks.
+#[derive(Debug)]
+pub struct BuddyAllocator {
+    /// Free block start frame numbers, per order.
+    free: [BTreeSet<u64>; MAX_ORDER],
+    /// Free frames across all orders.
+    free_frames: usize,
+}
+
+fn frame_number(frame: PhysFrame) -> u64 {
+    frame.start_address().as_u64() / 4096
+}
+
+fn frame_at(number: u64) -> PhysFrame {
+    PhysFrame::containing_address(PhysAddr::new(number * 4096))
+}
+
+/// Smallest order whose block holds `count` frames.
+pub fn order_for(count: usize) -> usize {
+    count.max(1).next_power_of_two().trailing_zeros() as usize
+}
+
+impl BuddyAllocator {
+    pub const fn new() -> Self {
+        const EMPTY: BTreeSet<u64> = BTreeSet::new();
+        BuddyAllocator { free: [EMPTY; MAX_ORDER], free_frames: 0 }
+    }
+
+    /// Free frames across all orders.
+    pub fn free_frames(&self) -> usize {
+        self.free_frames
+    }
+
+    /// Free blocks of `order`.
+    pub fn free_blocks(&self, order: usize) -> usize {
+        self.free[order].len()
+    }
+
+    /// Add the frames `[start, end)` (by address) as free memory.
+    pub fn add_region(&mut self, start: u64, end: u64) {
+        let mut frame = (start + 4095) / 4096;
+        let end = end / 4096;
+        while frame < end {
+            // Largest block aligned at `frame` that fits before `end`.
+            let mut order = (frame.trailing_zeros() as usize).min(MAX_ORDER - 1);
+            while frame + (1 << order) > end {
+                order -= 1;
+            }
+            self.free(frame_at(frame), order);
+            frame += 1 << order;
+        }
+    }
+
+    /// Allocate a block of `2^order` frames anywhere.
+    pub fn allocate(&mut self, order: usize) -> Option<PhysFrame> {
+        self.allocate_in(order, 0, u64::MAX)
+    }
+
+    /// Allocate a block of `2^order` frames lying entirely in `[low, high)` (by address).
+    pub fn allocate_in(&mut self, order: usize, low: u64, high: u64) -> Option<PhysFrame> {
+        if order >= MAX_ORDER {
+            return None;
+        }
+        let (low, high) = (low / 4096, high / 4096);
+
+        // Lowest-order free block that contains a fitting sub-block.
+        let (mut block, mut block_order) = (order..MAX_ORDER).find_map(|o| {
+            let first = low & !((1u64 << o) - 1);
+            self.free[o]
+                .range(first..)
+                .take_while(|&&b| b < high)
+                .find(|&&b| {
+                    let start = b.max(low).next_multiple_of(1 << order);
+                    start + (1 << order) <= (b + (1 << o)).min(high)
+                })
+                .map(|&b| (b, o))
+        })?;
+        self.free[block_order].remove(&block);
+
+        // Split down, keeping the half that holds the target range.
+        let target = block.max(low).next_multiple_of(1 << order);
+        while block_order > order {
+            block_order -= 1;
+            let upper = block + (1 << block_order);
+            if target >= upper {
+                self.free[block_order].insert(block);
+                block = upper;
+            } else {
+                self.free[block_order].insert(upper);
+            }
+        }
+
+        self.free_frames -= 1 << order;
+        Some(frame_at(block))
+    }
+
+    /// Return a block of `2^order` frames, merging it with free buddies.
+    pub fn free(&mut self, frame: PhysFrame, order: usize) {
+        let mut block = frame_number(frame);
+        let mut order = order;
+        debug_assert!(block % (1 << order) == 0, "buddy: misaligned free");
+        self.free_frames += 1 << order;
+
+        while order < MAX_ORDER - 1 {
+            let buddy = block ^ (1 << order);
+            if !self.free[order].remove(&buddy) {
+                break;
+            }
+            block = block.min(buddy);
+            order += 1;
+        }
+        self.free[order].insert(block);
+    }
+
+    /// Take a single frame out of the free sets, splitting the block holding it.
+    ///
+    /// Returns `false` if the frame is not free.
+    pub fn reserve(&mut self, frame: PhysFrame) -> bool {
+        let number = frame_number(frame);
+        self.allocate_in(0, number * 4096, (number + 1) * 4096).is_some()
+    }
+}
@@ -0,0 +1,83 @@
+//! Copies between kernel buffers and user memory (`memory/usercopy.rs`).
+//!
+//! - `copy_from_user`/`copy_to_user` walk a user range page by page
+//!   through a `Translate` (usually an `AddressSpace`'s tables).
+//! - Every page must be present and user-accessible, and writable for
+//!   `copy_to_user`. Bytes move through the physical-memory alias, so a bad
+//!   user pointer is an error and never a kernel page fault, whichever
+//!   address space is active.
+//! - On a bad page the copy stops there; the error says where and how many
+//!   bytes were already transferred, so a syscall can return a short count.
+//!
+//! Pages of an anonymous VMA that were never touched are not mapped yet and
+//! fail like any other hole; demand paging them in is the caller's choice.
+
+use x86_64::{
+    structures::paging::{mapper::TranslateResult, PageTableFlags, Translate},
+    VirtAddr,
+};
+
+use super::addr::{AddrError, UserVirtAddr};
+use super::phys_to_virt;
+
+/// Errors returned by `copy_from_user`/`copy_to_user`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserCopyError {
+    /// The range overflows or leaves the user window.
+    BadRange(AddrError),
+    /// The page at `addr` is not mapped or lacks the needed access;
+    /// `copied` bytes before it were transferred.
+    Fault { addr: VirtAddr, copied: usize },
+}
+
+/// Kernel alias of the user byte at `addr`, if its page allows the access.
+fn user_byte(table: &impl Translate, addr: VirtAddr, write: bool) -> Option<VirtAddr> {
+    let TranslateResult::Mapped { frame, offset, flags } = table.translate(addr) else {
+        return None;
+    };
+    // Only leaf flags are checked: user mappings are created with the same
+    // USER_ACCESSIBLE/WRITABLE bits on their parent entries.
+    let mut needed = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+    if write {
+        needed |= PageTableFlags::WRITABLE;
+    }
+    if !flags.contains(needed) {
+        return None;
+    }
+    Some(phys_to_virt(frame.start_address() + offset))
+}
+
+/// Call `f(kernel_ptr, buffer_offset, len)` for each page-bounded chunk of
+/// `[start, start + len)`, validating each page first.
+fn for_each_chunk(
+    table: &impl Translate,
+    start: UserVirtAddr,
+    len: usize,
+    write: bool,
+    mut f: impl FnMut(*mut u8, usize, usize),
+) -> Result<(), UserCopyError> {
+    start.range_end(len as u64).map_err(UserCopyError::BadRange)?;
+    let mut done = 0;
+    while done < len {
+        let addr = start.as_virt() + done as u64;
+        let chunk = (4096 - (addr.as_u64() % 4096) as usize).min(len - done);
+        let kernel = user_byte(table, addr, write).ok_or(UserCopyError::Fault { addr, copied: done })?;
+        f(kernel.as_mut_ptr(), done, chunk);
+        done += chunk;
+    }
+    Ok(())
+}
+
+/// Copy `dst.len()` bytes from user address `src` into `dst`.
+pub fn copy_from_user(table: &impl Translate, dst: &mut [u8], src: UserVirtAddr) -> Result<(), UserCopyError> {
+    for_each_chunk(table, src, dst.len(), false, |user, offset, len| unsafe {
+        core::ptr::copy_nonoverlapping(user, dst[offset..].as_mut_ptr(), len);
+    })
+}
+
+/// Copy `src` to user address `dst`.
+pub fn copy_to_user(table: &impl Translate, dst: UserVirtAddr, src: &[u8]) -> Result<(), UserCopyError> {
+    for_each_chunk(table, dst, src.len(), true, |user, offset, len| unsafe {
+        core::ptr::copy_nonoverlapping(src[offset..].as_ptr(), user, len);
+    })
+}
@@ -0,0 +1,129 @@
+//! Virtual memory areas (`memory/vma.rs`).
+//!
+//! - A `Vma` describes one page-aligned user range: its page flags and what
+//!   backs it (anonymous zero-fill memory or a fixed physical range).
+//! - `VmaSet` holds a process's areas keyed by start address, rejects
+//!   overlaps, and splits areas when part of a range is removed.
+//!
+//! The page fault handler and unmap paths consult this set instead of
+//! inferring intent from the raw page tables.
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use x86_64::{structures::paging::PageTableFlags, PhysAddr, VirtAddr};
+
+/// What provides the contents of an area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backing {
+    /// Zero-filled memory allocated on demand.
+    Anonymous,
+    /// A fixed physical range (device memory); `start` maps to this address.
+    Physical(PhysAddr),
+}
+
+/// One mapped user range, `[start, end)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vma {
+    pub start: VirtAddr,
+    pub end: VirtAddr,
+    /// Flags applied to each page (`PRESENT`/`USER_ACCESSIBLE` are implied).
+    pub flags: PageTableFlags,
+    pub backing: Backing,
+}
+
+impl Vma {
+    /// Whether `addr` lies inside the area.
+    pub fn contains(&self, addr: VirtAddr) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    /// Size in bytes.
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// The part of this area inside `[start, end)`, with backing adjusted.
+    fn slice(&self, start: VirtAddr, end: VirtAddr) -> Vma {
+        let start = start.max(self.start);
+        let end = end.min(self.end);
+        let backing = match self.backing {
+            Backing::Anonymous => Backing::Anonymous,
+            Backing::Physical(phys) => Backing::Physical(phys + (start - self.start)),
+        };
+        Vma { start, end, flags: self.flags, backing }
+    }
+}
+
+/// Errors returned by `VmaSet::insert`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VmaError {
+    /// Start or end is not page aligned, or the range is empty.
+    InvalidRange,
+    /// The range overlaps an existing area.
+    Overlap,
+}
+
+/// The areas of one address space.
+#[derive(Debug, Default)]
+pub struct VmaSet {
+    areas: BTreeMap<u64, Vma>,
+}
+
+impl VmaSet {
+    pub const fn new() -> Self {
+        VmaSet { areas: BTreeMap::new() }
+    }
+
+    /// Add `vma`, which must be page aligned and not overlap existing areas.
+    pub fn insert(&mut self, vma: Vma) -> Result<(), VmaError> {
+        if vma.start >= vma.end || !vma.start.is_aligned(4096u64) || !vma.end.is_aligned(4096u64) {
+            return Err(VmaError::InvalidRange);
+        }
+        if self.overlapping(vma.start, vma.end).next().is_some() {
+            return Err(VmaError::Overlap);
+        }
+        self.areas.insert(vma.start.as_u64(), vma);
+        Ok(())
+    }
+
+    /// The area containing `addr`, if any.
+    pub fn find(&self, addr: VirtAddr) -> Option<&Vma> {
+        self.areas
+            .range(..=addr.as_u64())
+            .next_back()
+            .map(|(_, vma)| vma)
+            .filter(|vma| vma.contains(addr))
+    }
+
+    /// Areas intersecting `[start, end)`.
+    pub fn overlapping(&self, start: VirtAddr, end: VirtAddr) -> impl Iterator<Item = &Vma> {
+        // The area before `start` may extend into the range.
+        let first = self.find(start).map(|vma| vma.start.as_u64()).unwrap_or(start.as_u64());
+        self.areas
+            .range(first..end.as_u64())
+            .map(|(_, vma)| vma)
+            .filter(move |vma| vma.end > start)
+    }
+
+    /// Remove `[start, end)`, trimming or splitting areas that straddle it.
+    pub fn remove_range(&mut self, start: VirtAddr, end: VirtAddr) {
+        let hit: alloc::vec::Vec<Vma> = self.overlapping(start, end).copied().collect();
+        for vma in hit {
+            self.areas.remove(&vma.start.as_u64());
+            if vma.start < start {
+                let head = vma.slice(vma.start, start);
+                self.areas.insert(head.start.as_u64(), head);
+            }
+            if vma.end > end {
+                let tail = vma.slice(end, vma.end);
+                self.areas.insert(tail.start.as_u64(), tail);
+            }
+        }
+    }
+
+    /// All areas in address order.
+    pub fn iter(&self) -> impl Iterator<Item = &Vma> {
+        self.areas.values()
+    }
+}
@@ -0,0 +1,236 @@
+//! Kernel page-protection audit (`memory/wx.rs`).
+//!
+//! - Walks every present mapping in the kernel's page tables and reports
+//!   pages that are both writable and executable, and pages reachable from
+//!   user mode.
+//! - Checks that the kernel image's read-only segments (text, rodata) are
+//!   not mapped writable, using the ELF program headers of the image the
+//!   bootloader loaded (`record_kernel_image`).
+//!
+//! Runs as a late initcall. Violations are logged as coalesced ranges; with
+//! feature `wx-strict` any violation fails the boot.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use log::{info, warn};
+use x86_64::{
+    registers::control::Cr3,
+    structures::paging::{PageTable, PageTableFlags},
+    PhysAddr,
+};
+
+use super::phys_to_virt;
+
+/// Physical address and length of the kernel ELF file, and its load offset.
+static KERNEL_ADDR: AtomicU64 = AtomicU64::new(0);
+static KERNEL_LEN: AtomicU64 = AtomicU64::new(0);
+static KERNEL_IMAGE_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Ranges logged per violation kind before only counting.
+const MAX_REPORTED: usize = 8;
+
+/// Remember where the bootloader placed the kernel image (from `BootInfo`).
+pub fn record_kernel_image(addr: u64, len: u64, image_offset: u64) {
+    KERNEL_ADDR.store(addr, Ordering::Relaxed);
+    KERNEL_LEN.store(len, Ordering::Relaxed);
+    KERNEL_IMAGE_OFFSET.store(image_offset, Ordering::Relaxed);
+}
+
+/// Kinds of protection violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Violation {
+    WritableExecutable,
+    UserAccessible,
+    WritableReadOnlySegment,
+}
+
+impl Violation {
+    const ALL: [Violation; 3] = [
+        Violation::WritableExecutable,
+        Violation::UserAccessible,
+        Violation::WritableReadOnlySegment,
+    ];
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Violation::WritableExecutable => "writable+executable",
+            Violation::UserAccessible => "user-accessible kernel page",
+            Violation::WritableReadOnlySegment => "writable read-only segment",
+        }
+    }
+}
+
+/// Coalesces violating pages of one kind into contiguous ranges.
+#[derive(Clone, Copy)]
+struct Tracker {
+    open: Option<(u64, u64)>,
+    ranges: usize,
+    bytes: u64,
+}
+
+impl Tracker {
+    const fn new() -> Self {
+        Tracker { open: None, ranges: 0, bytes: 0 }
+    }
+
+    fn add(&mut self, kind: Violation, start: u64, len: u64) {
+        self.bytes += len;
+        match self.open {
+            Some((s, e)) if e == start => self.open = Some((s, e + len)),
+            _ => {
+                self.flush(kind);
+                self.open = Some((start, start + len));
+            }
+        }
+    }
+
+    fn flush(&mut self, kind: Violation) {
+        if let Some((start, end)) = self.open.take() {
+            if self.ranges < MAX_REPORTED {
+                warn!("wx: {} at {:#x}..{:#x}", kind.describe(), start, end);
+            }
+            self.ranges += 1;
+        }
+    }
+}
+
+/// Effective permissions accumulated down the page-table walk.
+#[derive(Clone, Copy)]
+struct Access {
+    writable: bool,
+    user: bool,
+    executable: bool,
+}
+
+struct Audit {
+    trackers: [Tracker; 3],
+    /// Read-only segments of the kernel image: (start, end) virtual.
+    read_only: [(u64, u64); 8],
+    read_only_count: usize,
+}
+
+impl Audit {
+    fn leaf(&mut self, virt: u64, len: u64, access: Access) {
+        if access.writable && access.executable {
+            self.trackers[0].add(Violation::WritableExecutable, virt, len);
+        }
+        if access.user {
+            self.trackers[1].add(Violation::UserAccessible, virt, len);
+        }
+        if access.writable {
+            for &(start, end) in &self.read_only[..self.read_only_count] {
+                let (s, e) = (virt.max(start), (virt + len).min(end));
+                if s < e {
+                    self.trackers[2].add(Violation::WritableReadOnlySegment, s, e - s);
+                }
+            }
+        }
+    }
+
+    fn walk(&mut self, table: &PageTable, level: u8, base: u64, parent: Access) {
+        let shift = 12 + 9 * (level as u64 - 1);
+        for (i, entry) in table.iter().enumerate() {
+            let flags = entry.flags();
+            if !flags.contains(PageTableFlags::PRESENT) {
+                continue;
+            }
+
+            let mut virt = base | ((i as u64) << shift);
+            if level == 4 && i >= 256 {
+                virt |= 0xFFFF_0000_0000_0000; // sign-extend the upper half
+            }
+            let access = Access {
+                writable: parent.writable && flags.contains(PageTableFlags::WRITABLE),
+                user: parent.user && flags.contains(PageTableFlags::USER_ACCESSIBLE),
+                executable: parent.executable && !flags.contains(PageTableFlags::NO_EXECUTE),
+            };
+
+            if level == 1 || (level <= 3 && flags.contains(PageTableFlags::HUGE_PAGE)) {
+                self.leaf(virt, 1 << shift, access);
+            } else {
+                let next = unsafe { &*phys_to_virt(entry.addr()).as_ptr::<PageTable>() };
+                self.walk(next, level - 1, virt, access);
+            }
+        }
+    }
+}
+
+/// Collect the non-writable PT_LOAD segments of the kernel ELF image.
+fn read_only_segments(audit: &mut Audit) {
+    let addr = KERNEL_ADDR.load(Ordering::Relaxed);
+    let len = KERNEL_LEN.load(Ordering::Relaxed);
+    if addr == 0 || len < 64 {
+        warn!("wx: kernel image location unknown, skipping read-only segment check");
+        return;
+    }
+    let offset = KERNEL_IMAGE_OFFSET.load(Ordering::Relaxed);
+    let image = unsafe {
+        core::slice::from_raw_parts(phys_to_virt(PhysAddr::new(addr)).as_ptr::<u8>(), len as usize)
+    };
+
+    let u16_at = |o: usize| u16::from_le_bytes([image[o], image[o + 1]]) as usize;
+    let u32_at = |o: usize| u32::from_le_bytes(image[o..o + 4].try_into().unwrap());
+    let u64_at = |o: usize| u64::from_le_bytes(image[o..o + 8].try_into().unwrap());
+
+    if &image[..4] != b"\x7fELF" {
+        warn!("wx: kernel image is not ELF, skipping read-only segment check");
+        return;
+    }
+    let (phoff, phentsize, phnum) = (u64_at(0x20) as usize, u16_at(0x36), u16_at(0x38));
+
+    const PT_LOAD: u32 = 1;
+    const PF_W: u32 = 2;
+    for i in 0..phnum {
+        let ph = phoff + i * phentsize;
+        if ph + 0x30 > image.len() {
+            break;
+        }
+        if u32_at(ph) != PT_LOAD || u32_at(ph + 4) & PF_W != 0 {
+            continue;
+        }
+        if audit.read_only_count == audit.read_only.len() {
+            break;
+        }
+        let start = (u64_at(ph + 0x10) + offset) & !0xFFF;
+        let end = (u64_at(ph + 0x10) + offset + u64_at(ph + 0x28) + 0xFFF) & !0xFFF;
+        audit.read_only[audit.read_only_count] = (start, end);
+        audit.read_only_count += 1;
+    }
+}
+
+/// Audit the active kernel page tables and log any protection violations.
+pub fn wx_audit() {
+    let mut audit = Audit {
+        trackers: [Tracker::new(); 3],
+        read_only: [(0, 0); 8],
+        read_only_count: 0,
+    };
+    read_only_segments(&mut audit);
+
+    let (pml4, _) = Cr3::read();
+    let table = unsafe { &*phys_to_virt(pml4.start_address()).as_ptr::<PageTable>() };
+    let all = Access { writable: true, user: true, executable: true };
+    audit.walk(table, 4, 0, all);
+
+    let mut violations = 0;
+    for (tracker, kind) in audit.trackers.iter_mut().zip(Violation::ALL) {
+        tracker.flush(kind);
+        if tracker.ranges > 0 {
+            warn!(
+                "wx: {} range(s), {} KiB {}",
+                tracker.ranges,
+                tracker.bytes / 1024,
+                kind.describe()
+            );
+            violations += tracker.ranges;
+        }
+    }
+
+    if violations == 0 {
+        info!(
+            "wx: no violations ({} read-only kernel segment(s) checked)",
+            audit.read_only_count
+        );
+    } else if cfg!(feature = "wx-strict") {
+        panic!("wx: {} page-protection violation range(s)", violations);
+    }
+}
@@ -0,0 +1,9 @@
+//! Networking subsystem root (`net.rs`).
+//!
+//! - Home for protocol-independent helpers used by the software network path.
+//! - Re‑exports submodules (`checksum`) for packet processing primitives.
+//!
+//! There is no NIC driver or protocol stack yet; these helpers are written
+//! first because they sit on every packet's hot path.
+
+pub mod checksum;
@@ -0,0 +1,135 @@
+//! Internet one's-complement checksum (`net/checksum.rs`).
+//!
+//! - `sum` accumulates RFC 1071 partial sums, so pseudo-headers and payloads
+//!   can be summed separately and combined with `finish`.
+//! - `checksum` is the one-shot helper for a single contiguous buffer.
+//! - Two implementations: portable scalar and SSE2 (16 bytes per step). The
+//!   SSE2 path is chosen at runtime from CPUID and the CR0/CR4 state, since
+//!   the kernel target does not assume the FPU/SSE unit is enabled.
+//!
+//! Words are summed in native (little-endian) order; RFC 1071 §2(B) allows
+//! this as long as the folded result is byte-swapped, which `finish` does.
+
+use core::arch::x86_64::{
+    __cpuid, __m128i, _mm_add_epi32, _mm_loadu_si128, _mm_setzero_si128, _mm_storeu_si128,
+    _mm_unpackhi_epi16, _mm_unpacklo_epi16,
+};
+use core::sync::atomic::{AtomicU8, Ordering};
+use x86_64::registers::control::{Cr0, Cr0Flags, Cr4, Cr4Flags};
+
+/// Checksum implementation in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Scalar,
+    Sse2,
+}
+
+const UNKNOWN: u8 = 0;
+const SCALAR: u8 = 1;
+const SSE2: u8 = 2;
+
+/// Cached result of `backend()` detection.
+static BACKEND: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+/// SSE2 blocks summed before the 32-bit lanes are flushed.
+/// Each block adds at most 2 * 0xFFFF per lane, so 16384 blocks cannot overflow.
+const SSE2_FLUSH_BLOCKS: usize = 16384;
+
+/// Detect (once) which implementation this CPU can run.
+pub fn backend() -> Backend {
+    match BACKEND.load(Ordering::Relaxed) {
+        SCALAR => Backend::Scalar,
+        SSE2 => Backend::Sse2,
+        _ => {
+            let detected = if sse2_usable() { Backend::Sse2 } else { Backend::Scalar };
+            BACKEND.store(
+                if detected == Backend::Sse2 { SSE2 } else { SCALAR },
+                Ordering::Relaxed,
+            );
+            detected
+        }
+    }
+}
+
+/// SSE2 needs CPU support (CPUID.1:EDX[26]) and the OS side enabled:
+/// CR4.OSFXSR set and CR0.EM clear, otherwise SSE instructions fault with #UD.
+fn sse2_usable() -> bool {
+    let cpu = unsafe { __cpuid(1) }.edx & (1 << 26) != 0;
+    let enabled = Cr4::read().contains(Cr4Flags::OSFXSR)
+        && !Cr0::read().contains(Cr0Flags::EMULATE_COPROCESSOR);
+    cpu && enabled
+}
+
+/// Add `data` to a running partial sum `initial`.
+///
+/// Every call except the last must cover an even number of bytes.
+pub fn sum(data: &[u8], initial: u64) -> u64 {
+    match backend() {
+        Backend::Sse2 => unsafe { sum_sse2(data, initial) },
+        Backend::Scalar => sum_scalar(data, initial),
+    }
+}
+
+/// Fold a partial sum and complement it. The result is in host order;
+/// store it with `to_be_bytes`.
+pub fn finish(sum: u64) -> u16 {
+    let mut sum = sum;
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16).swap_bytes()
+}
+
+/// Internet checksum of one buffer.
+pub fn checksum(data: &[u8]) -> u16 {
+    finish(sum(data, 0))
+}
+
+/// Portable implementation: one 16-bit word at a time.
+pub fn sum_scalar(data: &[u8], initial: u64) -> u64 {
+    let mut sum = initial;
+    let mut words = data.chunks_exact(2);
+    for word in &mut words {
+        sum += u16::from_le_bytes([word[0], word[1]]) as u64;
+    }
+    // An odd trailing byte is padded with zero (high-order byte on the wire).
+    if let [last] = words.remainder() {
+        sum += *last as u64;
+    }
+    sum
+}
+
+/// SSE2 implementation: widens eight words per 16-byte block into four
+/// 32-bit lanes and sums those, flushing into `u64` before they can overflow.
+///
+/// # Safety
+/// SSE2 must be usable (see `backend`).
+#[target_feature(enable = "sse2")]
+pub unsafe fn sum_sse2(data: &[u8], initial: u64) -> u64 {
+    let mut sum = initial;
+    let mut blocks = data.chunks_exact(16);
+    let zero = _mm_setzero_si128();
+
+    loop {
+        let mut acc = _mm_setzero_si128();
+        let mut n = 0;
+        while n < SSE2_FLUSH_BLOCKS {
+            let Some(block) = blocks.next() else { break };
+            let v = unsafe { _mm_loadu_si128(block.as_ptr() as *const __m128i) };
+            acc = _mm_add_epi32(acc, _mm_unpacklo_epi16(v, zero));
+            acc = _mm_add_epi32(acc, _mm_unpackhi_epi16(v, zero));
+            n += 1;
+        }
+
+        let mut lanes = [0u32; 4];
+        unsafe { _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, acc) };
+        sum += lanes.iter().map(|&l| l as u64).sum::<u64>();
+
+        if n < SSE2_FLUSH_BLOCKS {
+            break;
+        }
+    }
+
+    // Blocks are 16 bytes, so the tail starts on a word boundary.
+    sum_scalar(blocks.remainder(), sum)
+}
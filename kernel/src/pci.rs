@@ -0,0 +1,128 @@
+//! PCI bus enumeration (`pci.rs`).
+//!
+//! - Accesses configuration space through the legacy 0xCF8/0xCFC mechanism.
+//! - Brute-force scans every bus/slot/function and registers each present
+//!   function with the driver model (`driver::register_device`).
+//! - Functions with the virtio vendor ID are registered on `Bus::Virtio`.
+
+use spin::Mutex;
+use log::{info, debug};
+
+use crate::driver::{self, Bus, Device, PciAddress};
+use crate::ioport::{self, IoPortRange};
+
+/// Configuration address/data port pair (0xCF8–0xCFF).
+static CONFIG_PORTS: Mutex<Option<IoPortRange>> = Mutex::new(None);
+
+/// Vendor ID used by virtio devices.
+pub const VIRTIO_VENDOR_ID: u16 = 0x1AF4;
+
+/// Offset of the data register within the claimed range.
+const CONFIG_DATA: u16 = 4;
+
+/// Read a 32-bit register from configuration space.
+/// `offset` is rounded down to a 4-byte boundary.
+pub fn config_read(addr: PciAddress, offset: u8) -> u32 {
+    let ports = CONFIG_PORTS.lock();
+    let ports = ports.as_ref().expect("pci: config ports not claimed");
+    ports.write::<u32>(0, config_address(addr, offset));
+    ports.read::<u32>(CONFIG_DATA)
+}
+
+/// Write a 32-bit register in configuration space.
+pub fn config_write(addr: PciAddress, offset: u8, value: u32) {
+    let ports = CONFIG_PORTS.lock();
+    let ports = ports.as_ref().expect("pci: config ports not claimed");
+    ports.write::<u32>(0, config_address(addr, offset));
+    ports.write::<u32>(CONFIG_DATA, value);
+}
+
+/// Build the CONFIG_ADDRESS value (enable bit + bus/slot/function/register).
+fn config_address(addr: PciAddress, offset: u8) -> u32 {
+    (1 << 31)
+        | ((addr.bus as u32) << 16)
+        | ((addr.slot as u32) << 11)
+        | ((addr.function as u32) << 8)
+        | ((offset as u32) & 0xFC)
+}
+
+/// Read base address register `index` (0–5) of a function.
+pub fn read_bar(addr: PciAddress, index: u8) -> u32 {
+    config_read(addr, 0x10 + index * 4)
+}
+
+/// Short description for common class/subclass pairs.
+fn class_name(class: u8, subclass: u8) -> &'static str {
+    match (class, subclass) {
+        (0x01, 0x01) => "ide-controller",
+        (0x01, 0x06) => "sata-controller",
+        (0x01, _) => "storage",
+        (0x02, _) => "network",
+        (0x03, _) => "display",
+        (0x06, 0x00) => "host-bridge",
+        (0x06, 0x01) => "isa-bridge",
+        (0x06, _) => "bridge",
+        (0x0C, 0x03) => "usb-controller",
+        _ => "pci-device",
+    }
+}
+
+/// Scan all of configuration space and register each function found.
+pub fn enumerate() {
+    info!("Enumerating PCI bus");
+    match ioport::claim(0xCF8, 8, "pci-config") {
+        Ok(ports) => *CONFIG_PORTS.lock() = Some(ports),
+        Err(e) => {
+            info!("PCI configuration ports unavailable: {:?}", e);
+            return;
+        }
+    }
+
+    let mut found = 0;
+    for bus in 0..=255u8 {
+        for slot in 0..32u8 {
+            for function in 0..8u8 {
+                let addr = PciAddress { bus, slot, function };
+                let id = config_read(addr, 0x00);
+                let vendor = (id & 0xFFFF) as u16;
+                if vendor == 0xFFFF {
+                    if function == 0 {
+                        break; // no device in this slot
+                    }
+                    continue;
+                }
+
+                let class_reg = config_read(addr, 0x08);
+                let class = (class_reg >> 24) as u8;
+                let subclass = (class_reg >> 16) as u8;
+                let irq_line = (config_read(addr, 0x3C) & 0xFF) as u8;
+
+                let dev = Device {
+                    name: class_name(class, subclass),
+                    bus: if vendor == VIRTIO_VENDOR_ID { Bus::Virtio } else { Bus::Pci },
+                    vendor,
+                    device: (id >> 16) as u16,
+                    class,
+                    subclass,
+                    pci: Some(addr),
+                    irq: if irq_line == 0xFF { None } else { Some(irq_line) },
+                    ..Device::platform("")
+                };
+                debug!(
+                    "PCI {:02x}:{:02x}.{} {:04x}:{:04x} class={:02x}:{:02x}",
+                    bus, slot, function, dev.vendor, dev.device, class, subclass
+                );
+                driver::register_device(dev);
+                found += 1;
+
+                // Single-function devices only implement function 0.
+                let header_type = (config_read(addr, 0x0C) >> 16) as u8;
+                if function == 0 && header_type & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    info!("PCI enumeration complete: {} functions", found);
+}
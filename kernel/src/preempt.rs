@@ -0,0 +1,94 @@
+//! Kernel preemption control (`preempt.rs`).
+//!
+//! - `preempt_disable`/`preempt_enable` maintain a nesting counter that the
+//!   timer-driven scheduler must consult before switching tasks.
+//! - `PreemptGuard` disables preemption for the lifetime of a scope.
+//! - `might_sleep` asserts (in debug builds) that the caller is preemptible,
+//!   naming both the sleeping call site and where preemption was disabled.
+//!
+//! Bulldog currently runs a single kernel thread on one CPU, so the counter is
+//! global; it becomes per-thread once a scheduler exists.
+
+use core::panic::Location;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// Preemption-disable nesting depth.
+static PREEMPT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Call site of the outermost `preempt_disable` (null when preemptible).
+static DISABLED_AT: AtomicPtr<Location<'static>> = AtomicPtr::new(ptr::null_mut());
+
+/// Disable preemption. Calls nest; each must be paired with `preempt_enable`.
+#[track_caller]
+pub fn preempt_disable() {
+    if PREEMPT_COUNT.fetch_add(1, Ordering::Acquire) == 0 {
+        let caller = Location::caller() as *const Location<'static> as *mut Location<'static>;
+        DISABLED_AT.store(caller, Ordering::Relaxed);
+    }
+}
+
+/// Re-enable preemption once the outermost disable is undone.
+#[track_caller]
+pub fn preempt_enable() {
+    let previous = PREEMPT_COUNT.fetch_sub(1, Ordering::Release);
+    assert!(previous > 0, "preempt_enable without matching disable at {}", Location::caller());
+    if previous == 1 {
+        DISABLED_AT.store(ptr::null_mut(), Ordering::Relaxed);
+    }
+}
+
+/// Current nesting depth (0 means preemptible).
+pub fn preempt_count() -> usize {
+    PREEMPT_COUNT.load(Ordering::Relaxed)
+}
+
+/// Whether the scheduler may switch away from the current context.
+pub fn preemptible() -> bool {
+    preempt_count() == 0
+}
+
+/// Where preemption was (outermost) disabled, if it currently is.
+pub fn disabled_at() -> Option<&'static Location<'static>> {
+    let location = DISABLED_AT.load(Ordering::Relaxed);
+    unsafe { location.as_ref() }
+}
+
+/// Assert that the caller may sleep.
+///
+/// Panics in debug builds if preemption is disabled, naming the caller and
+/// the site that disabled preemption.
+#[track_caller]
+pub fn might_sleep() {
+    if cfg!(debug_assertions) && !preemptible() {
+        match disabled_at() {
+            Some(site) => panic!(
+                "sleeping with preemption disabled at {} (disabled at {}, depth {})",
+                Location::caller(),
+                site,
+                preempt_count()
+            ),
+            None => panic!("sleeping with preemption disabled at {}", Location::caller()),
+        }
+    }
+}
+
+/// RAII guard: preemption stays disabled until the guard is dropped.
+pub struct PreemptGuard {
+    _private: (),
+}
+
+impl PreemptGuard {
+    /// Disable preemption until the returned guard goes out of scope.
+    #[track_caller]
+    pub fn new() -> Self {
+        preempt_disable();
+        PreemptGuard { _private: () }
+    }
+}
+
+impl Drop for PreemptGuard {
+    fn drop(&mut self) {
+        preempt_enable();
+    }
+}
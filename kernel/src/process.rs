@@ -0,0 +1,451 @@
+//! Processes and PID management (`process.rs`).
+//!
+//! - `Process` is the process control block: PID, state, file-descriptor
+//!   table, page-table root and exit code.
+//! - A global table maps PIDs to processes; `current` names the one running.
+//! - `exit` turns the current process into a zombie holding its exit code
+//!   until `reap` collects it.
+//! - User processes own an `AddressSpace` and a `VmaSet` describing every
+//!   user mapping; `brk` grows or shrinks the heap and its VMA together.
+//! - `handle_page_fault` backs not-present faults inside anonymous VMAs
+//!   with zeroed pages on first touch.
+//! - Each process carries `Credentials` with a capability set; privileged
+//!   operations call `capable` before acting. Children inherit the creator's
+//!   set, and `drop_capabilities` removes bits irrevocably.
+//!
+//! PID 0 is the kernel itself, adopted from the running context by `init`.
+//! There is no scheduler yet, so nothing switches `CURRENT` away from it.
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+use log::info;
+use spin::Mutex;
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::{Page, PageTableFlags, PhysFrame, Size4KiB};
+use x86_64::VirtAddr;
+
+use crate::memory::addr::UserVirtAddr;
+use crate::memory::address_space::{AddressSpace, AddressSpaceError};
+use crate::memory::vma::{Backing, Vma, VmaSet};
+
+/// Process identifier.
+pub type Pid = u32;
+
+/// PID of the kernel's own process.
+pub const KERNEL_PID: Pid = 0;
+
+/// Maximum open file descriptors per process.
+pub const MAX_FDS: usize = 64;
+
+/// Start of every user process's heap (`brk` region).
+pub const USER_HEAP_BASE: u64 = 0x0000_0100_0000_0000;
+
+/// Largest a user heap may grow.
+pub const USER_HEAP_MAX: u64 = 1024 * 1024 * 1024;
+
+/// Lifecycle state of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    /// Runnable, waiting for the CPU.
+    Ready,
+    /// Currently executing.
+    Running,
+    /// Waiting on an event.
+    Blocked,
+    /// Exited; holds its exit code until reaped.
+    Zombie,
+}
+
+impl ProcessState {
+    /// Short name used in listings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcessState::Ready => "ready",
+            ProcessState::Running => "running",
+            ProcessState::Blocked => "blocked",
+            ProcessState::Zombie => "zombie",
+        }
+    }
+}
+
+/// A set of capability bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// Administrative operations: sysctl writes, mount, reboot, chroot, raw devices.
+    pub const SYS_ADMIN: Capabilities = Capabilities(1 << 0);
+    /// Network interface and routing configuration.
+    pub const NET_ADMIN: Capabilities = Capabilities(1 << 1);
+    /// Setting the system clock.
+    pub const SYS_TIME: Capabilities = Capabilities(1 << 2);
+    /// Signalling processes owned by others.
+    pub const KILL: Capabilities = Capabilities(1 << 3);
+
+    /// No capabilities.
+    pub const fn empty() -> Self {
+        Capabilities(0)
+    }
+
+    /// Every capability.
+    pub const fn all() -> Self {
+        Capabilities(0b1111)
+    }
+
+    /// Whether every bit of `other` is present.
+    pub fn contains(&self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// `self` without the bits in `other`.
+    pub fn without(self, other: Capabilities) -> Self {
+        Capabilities(self.0 & !other.0)
+    }
+
+    /// Short name of a single capability, for logging.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Capabilities::SYS_ADMIN => "CAP_SYS_ADMIN",
+            Capabilities::NET_ADMIN => "CAP_NET_ADMIN",
+            Capabilities::SYS_TIME => "CAP_SYS_TIME",
+            Capabilities::KILL => "CAP_KILL",
+            _ => "CAP_(set)",
+        }
+    }
+}
+
+/// Security credentials of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Credentials {
+    pub caps: Capabilities,
+}
+
+/// Object behind a file descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileObject {
+    /// The framebuffer console (stdin/stdout/stderr by default).
+    Console,
+}
+
+impl FileObject {
+    /// Write `bytes` to the object; returns how many were written.
+    pub fn write(&self, bytes: &[u8]) -> Result<usize, crate::console::ConsoleError> {
+        match self {
+            FileObject::Console => crate::console::write_bytes(bytes),
+        }
+    }
+}
+
+/// Per-process file-descriptor table.
+#[derive(Debug, Clone)]
+pub struct FdTable {
+    slots: Vec<Option<FileObject>>,
+}
+
+impl FdTable {
+    /// A table with fds 0, 1 and 2 open on the console.
+    pub fn with_stdio() -> Self {
+        let mut slots = Vec::new();
+        slots.resize(3, Some(FileObject::Console));
+        FdTable { slots }
+    }
+
+    /// Install `object` at the lowest free descriptor.
+    pub fn open(&mut self, object: FileObject) -> Option<usize> {
+        if let Some(fd) = self.slots.iter().position(|slot| slot.is_none()) {
+            self.slots[fd] = Some(object);
+            return Some(fd);
+        }
+        if self.slots.len() >= MAX_FDS {
+            return None;
+        }
+        self.slots.push(Some(object));
+        Some(self.slots.len() - 1)
+    }
+
+    /// Object behind `fd`, if open.
+    pub fn get(&self, fd: usize) -> Option<FileObject> {
+        self.slots.get(fd).copied().flatten()
+    }
+
+    /// Close `fd`; returns the object it referred to.
+    pub fn close(&mut self, fd: usize) -> Option<FileObject> {
+        self.slots.get_mut(fd)?.take()
+    }
+
+    /// Number of open descriptors.
+    pub fn open_count(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+}
+
+/// Process control block.
+#[derive(Debug)]
+pub struct Process {
+    pub pid: Pid,
+    pub name: &'static str,
+    pub state: ProcessState,
+    pub fds: FdTable,
+    /// Level-4 page table loaded into CR3 when this process runs.
+    pub page_table: PhysFrame,
+    /// Private mappings; `None` for the kernel process.
+    pub address_space: Option<AddressSpace>,
+    /// User memory areas in `address_space`.
+    pub vmas: VmaSet,
+    /// Current program break (end of the heap); starts at `USER_HEAP_BASE`.
+    pub brk: u64,
+    pub creds: Credentials,
+    /// Set once the process has exited.
+    pub exit_code: Option<i32>,
+}
+
+/// All live and zombie processes.
+static PROCESSES: Mutex<BTreeMap<Pid, Process>> = Mutex::new(BTreeMap::new());
+
+/// PID of the running process.
+static CURRENT: AtomicU32 = AtomicU32::new(KERNEL_PID);
+
+/// Next PID to hand out.
+static NEXT_PID: AtomicU32 = AtomicU32::new(KERNEL_PID + 1);
+
+/// Register the running kernel context as PID 0.
+pub fn init() {
+    let (page_table, _) = Cr3::read();
+    PROCESSES.lock().insert(
+        KERNEL_PID,
+        Process {
+            pid: KERNEL_PID,
+            name: "kernel",
+            state: ProcessState::Running,
+            fds: FdTable::with_stdio(),
+            page_table,
+            address_space: None,
+            vmas: VmaSet::new(),
+            brk: USER_HEAP_BASE,
+            creds: Credentials { caps: Capabilities::all() },
+            exit_code: None,
+        },
+    );
+    info!("process: kernel registered as pid {}", KERNEL_PID);
+}
+
+/// Create a ready process that will run in `address_space`.
+/// It inherits the credentials of the running process.
+pub fn create(name: &'static str, address_space: AddressSpace) -> Pid {
+    let pid = NEXT_PID.fetch_add(1, Ordering::Relaxed);
+    let creds = with_current(|parent| parent.creds);
+    PROCESSES.lock().insert(
+        pid,
+        Process {
+            pid,
+            name,
+            state: ProcessState::Ready,
+            fds: FdTable::with_stdio(),
+            page_table: address_space.pml4(),
+            address_space: Some(address_space),
+            vmas: VmaSet::new(),
+            brk: USER_HEAP_BASE,
+            creds,
+            exit_code: None,
+        },
+    );
+    pid
+}
+
+/// PID of the running process.
+pub fn current() -> Pid {
+    CURRENT.load(Ordering::Relaxed)
+}
+
+/// Run `f` on the running process's control block.
+pub fn with_current<R>(f: impl FnOnce(&mut Process) -> R) -> R {
+    let pid = current();
+    let mut processes = PROCESSES.lock();
+    let process = processes.get_mut(&pid).expect("process: current pid not in table");
+    f(process)
+}
+
+/// Terminate the running process with `code`, leaving a zombie to reap.
+///
+/// Closes its descriptors. The kernel process cannot exit.
+pub fn exit(code: i32) {
+    let pid = current();
+    if pid == KERNEL_PID {
+        panic!("process: kernel process exited with code {}", code);
+    }
+    with_current(|process| {
+        process.state = ProcessState::Zombie;
+        process.exit_code = Some(code);
+        process.fds = FdTable { slots: Vec::new() };
+    });
+    info!("process: pid {} exited with code {}", pid, code);
+}
+
+/// Errors returned by `brk`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BrkError {
+    /// The kernel process has no user heap.
+    NoAddressSpace,
+    /// Below `USER_HEAP_BASE` or beyond `USER_HEAP_MAX`.
+    InvalidAddress,
+    /// Mapping a heap page failed.
+    Map(AddressSpaceError),
+}
+
+/// Move the running process's program break to `new_end`, or query it with `None`.
+///
+/// Growing maps zeroed, writable, non-executable pages; shrinking unmaps
+/// whole pages above the new break. On failure the break is unchanged.
+pub fn brk(new_end: Option<UserVirtAddr>) -> Result<UserVirtAddr, BrkError> {
+    with_current(|process| {
+        let Some(space) = process.address_space.as_mut() else {
+            return Err(BrkError::NoAddressSpace);
+        };
+        let old_end = process.brk;
+        let Some(new_end) = new_end.map(|a| a.as_u64()) else {
+            return Ok(UserVirtAddr::new(old_end).expect("program break outside the user window"));
+        };
+        if new_end < USER_HEAP_BASE || new_end > USER_HEAP_BASE + USER_HEAP_MAX {
+            return Err(BrkError::InvalidAddress);
+        }
+
+        let page_at = |addr: u64| Page::<Size4KiB>::containing_address(VirtAddr::new(addr));
+        let old_top = page_at(old_end + 4095);
+        let new_top = page_at(new_end + 4095);
+
+        if new_top > old_top {
+            let flags = PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+            for page in Page::range(old_top, new_top) {
+                if let Err(e) = space.map_user(page, flags) {
+                    // Roll back what this call mapped.
+                    for mapped in Page::range(old_top, page) {
+                        let _ = space.unmap_user(mapped);
+                    }
+                    return Err(BrkError::Map(e));
+                }
+            }
+        } else {
+            for page in Page::range(new_top, old_top) {
+                let _ = space.unmap_user(page);
+            }
+        }
+
+        // The heap VMA always spans [USER_HEAP_BASE, new_top).
+        let heap_base = VirtAddr::new(USER_HEAP_BASE);
+        process.vmas.remove_range(heap_base, heap_base + USER_HEAP_MAX);
+        if new_top.start_address() > heap_base {
+            let heap = Vma {
+                start: heap_base,
+                end: new_top.start_address(),
+                flags: PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE,
+                backing: Backing::Anonymous,
+            };
+            process.vmas.insert(heap).expect("heap VMA overlaps another mapping");
+        }
+
+        process.brk = new_end;
+        Ok(UserVirtAddr::new(new_end).expect("program break outside the user window"))
+    })
+}
+
+/// Reasons a page fault could not be resolved by `handle_page_fault`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FaultError {
+    /// The address is outside the user window: a kernel fault.
+    NotUser,
+    /// The process table is locked (the fault hit code holding it).
+    Busy,
+    /// The faulting address space is not the running process's.
+    ForeignAddressSpace,
+    /// No VMA covers the address.
+    NoVma,
+    /// The access is not allowed by the VMA (write to read-only, fetch from NX),
+    /// or the page is present and the fault is a protection violation.
+    AccessViolation,
+    /// The VMA is not demand-paged (physical backings are mapped up front).
+    NotDemandPaged,
+    /// Mapping the page failed (including `Busy`/`OutOfFrames` when the
+    /// fault reserve is locked or empty).
+    Map(AddressSpaceError),
+}
+
+/// Resolve a fault at `addr` in the running process by mapping a zeroed page
+/// from its covering anonymous VMA.
+///
+/// `present`, `write` and `fetch` come from the fault's error code. Every
+/// lock is taken with `try_lock` and frames come from the fault reserve, so
+/// a fault inside code holding the process table or the reserve is reported
+/// (`Busy`) rather than deadlocking, and the heap is never touched.
+pub fn handle_page_fault(addr: VirtAddr, present: bool, write: bool, fetch: bool) -> Result<(), FaultError> {
+    let addr = UserVirtAddr::try_from(addr).map_err(|_| FaultError::NotUser)?.as_virt();
+    let pid = current();
+    let mut processes = PROCESSES.try_lock().ok_or(FaultError::Busy)?;
+    let process = processes.get_mut(&pid).ok_or(FaultError::NoVma)?;
+    if process.page_table != Cr3::read().0 {
+        return Err(FaultError::ForeignAddressSpace);
+    }
+
+    let vma = *process.vmas.find(addr).ok_or(FaultError::NoVma)?;
+    if present
+        || (write && !vma.flags.contains(PageTableFlags::WRITABLE))
+        || (fetch && vma.flags.contains(PageTableFlags::NO_EXECUTE))
+    {
+        return Err(FaultError::AccessViolation);
+    }
+    if vma.backing != Backing::Anonymous {
+        return Err(FaultError::NotDemandPaged);
+    }
+
+    let space = process.address_space.as_mut().ok_or(FaultError::ForeignAddressSpace)?;
+    let page = Page::<Size4KiB>::containing_address(addr);
+    match space.map_user_reserved(page, vma.flags) {
+        // Another path mapped it between the fault and now.
+        Ok(_) | Err(AddressSpaceError::AlreadyMapped) => Ok(()),
+        Err(e) => Err(FaultError::Map(e)),
+    }
+}
+
+/// Whether the running process holds `cap` for operation `op`.
+/// Denials are recorded in the audit log.
+///
+/// Before `init` registers PID 0, the caller is boot code and is granted everything.
+pub fn capable(cap: Capabilities, op: &'static str) -> bool {
+    let pid = current();
+    let granted = PROCESSES.lock().get(&pid).map_or(true, |p| p.creds.caps.contains(cap));
+    if !granted {
+        crate::audit::record(crate::audit::AuditEvent::CapabilityDenied(cap), op);
+    }
+    granted
+}
+
+/// Permanently remove `caps` from the running process.
+pub fn drop_capabilities(caps: Capabilities) {
+    with_current(|p| p.creds.caps = p.creds.caps.without(caps));
+}
+
+/// Remove a zombie from the table and return its exit code.
+pub fn reap(pid: Pid) -> Option<i32> {
+    let mut processes = PROCESSES.lock();
+    let code = processes.get(&pid).filter(|p| p.state == ProcessState::Zombie)?.exit_code;
+    processes.remove(&pid);
+    code
+}
+
+/// Log every process, `ps`-style.
+pub fn log_processes() {
+    let processes = PROCESSES.lock();
+    info!("{:>5} {:<8} {:<16} {:>4} {:>18}", "PID", "STATE", "NAME", "FDS", "CR3");
+    for p in processes.values() {
+        info!(
+            "{:>5} {:<8} {:<16} {:>4} {:#18x}",
+            p.pid,
+            p.state.name(),
+            p.name,
+            p.fds.open_count(),
+            p.page_table.start_address().as_u64()
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Read-copy-update-lite for mostly-read kernel tables (`rcu.rs`).
+//!
+//! - Readers enter a read-side section (`RcuCell::read`) without taking a lock.
+//! - Writers publish a fresh copy (`replace`/`update`) and retire the old one.
+//! - Retired values are freed at quiescent points (`quiescent`, called from the
+//!   idle loop and later from context switch) once no reader can still see them.
+//!
+//! Current user: the sysctl hostname. The mount and syscall tables will move
+//! here once they exist.
+//!
+//! Grace periods use two alternating epochs, each with a reader count. The
+//! epoch only advances when the slot it would reuse has drained, so a value
+//! retired two epochs ago is guaranteed to be unreachable.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use spin::Mutex;
+
+/// Current grace-period epoch.
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// Active readers per epoch parity.
+static READERS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+
+/// Values awaiting reclamation, tagged with the epoch they were retired in.
+static RETIRED: Mutex<Vec<(u64, Box<dyn Send>)>> = Mutex::new(Vec::new());
+
+/// Read-side critical section; readers registered in its epoch block reclamation.
+pub struct RcuReadGuard {
+    slot: usize,
+}
+
+/// Enter a read-side critical section.
+pub fn read_lock() -> RcuReadGuard {
+    loop {
+        let epoch = EPOCH.load(Ordering::SeqCst);
+        let slot = (epoch & 1) as usize;
+        READERS[slot].fetch_add(1, Ordering::SeqCst);
+
+        // If the epoch advanced meanwhile we may have joined a slot that is being drained.
+        if EPOCH.load(Ordering::SeqCst) == epoch {
+            return RcuReadGuard { slot };
+        }
+        READERS[slot].fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Drop for RcuReadGuard {
+    fn drop(&mut self) {
+        READERS[self.slot].fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Hand `value` over for deferred destruction.
+fn retire(value: Box<dyn Send>) {
+    let epoch = EPOCH.load(Ordering::SeqCst);
+    RETIRED.lock().push((epoch, value));
+}
+
+/// Report a quiescent state: try to end the grace period and reclaim retired values.
+///
+/// Cheap when nothing is retired; safe to call from the idle loop.
+pub fn quiescent() {
+    let epoch = EPOCH.load(Ordering::SeqCst);
+
+    // The slot the next epoch reuses still holds readers from `epoch - 1`.
+    if READERS[((epoch + 1) & 1) as usize].load(Ordering::SeqCst) != 0 {
+        return;
+    }
+    EPOCH.store(epoch + 1, Ordering::SeqCst);
+
+    // Everything retired before `epoch` is now unreachable.
+    let reclaimable: Vec<(u64, Box<dyn Send>)> = {
+        let mut retired = RETIRED.lock();
+        if retired.is_empty() {
+            return;
+        }
+        let (done, pending) = core::mem::take(&mut *retired)
+            .into_iter()
+            .partition(|(retired_at, _)| *retired_at < epoch);
+        *retired = pending;
+        done
+    };
+
+    // Drop outside the lock in case a destructor retires more values.
+    drop(reclaimable);
+}
+
+/// A pointer to an immutable `T` that readers access lock-free.
+pub struct RcuCell<T: Send + Sync + 'static> {
+    ptr: AtomicPtr<T>,
+    /// Serializes writers; readers never take it.
+    writer: Mutex<()>,
+}
+
+impl<T: Send + Sync + 'static> RcuCell<T> {
+    /// Create a cell holding `value`.
+    pub fn new(value: T) -> Self {
+        RcuCell {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Run `f` on the current value inside a read-side section.
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let _guard = read_lock();
+        let current = unsafe { &*self.ptr.load(Ordering::SeqCst) };
+        f(current)
+    }
+
+    /// Publish `value`, retiring the previous one.
+    pub fn replace(&self, value: T) {
+        let _writer = self.writer.lock();
+        self.swap_in(value);
+    }
+
+    /// Copy-update: build a new value from the current one and publish it.
+    pub fn update(&self, f: impl FnOnce(&T) -> T) {
+        let _writer = self.writer.lock();
+        let new = f(unsafe { &*self.ptr.load(Ordering::SeqCst) });
+        self.swap_in(new);
+    }
+
+    fn swap_in(&self, value: T) {
+        let old = self.ptr.swap(Box::into_raw(Box::new(value)), Ordering::SeqCst);
+        retire(unsafe { Box::from_raw(old) });
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for RcuCell<T> {
+    fn drop(&mut self) {
+        // Readers may still hold the last value; defer it like any other.
+        retire(unsafe { Box::from_raw(*self.ptr.get_mut()) });
+    }
+}
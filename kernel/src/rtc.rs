@@ -0,0 +1,95 @@
+//! CMOS real-time clock (`rtc.rs`).
+//!
+//! - Claims the CMOS index/data ports (0x70–0x71).
+//! - `seconds_of_day` reads the wall-clock time, handling BCD and 12-hour
+//!   encodings and retrying until two consecutive reads agree.
+//!
+//! The RTC only has one-second resolution; it serves as an independent
+//! reference for calibrating the LAPIC tick and the TSC (`time::calibrate`).
+
+use spin::Mutex;
+use log::error;
+
+use crate::ioport::{self, IoPortRange};
+
+/// CMOS index/data ports, once `init` has run.
+static CMOS_PORTS: Mutex<Option<IoPortRange>> = Mutex::new(None);
+
+// CMOS register indices.
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+
+/// Keep NMIs disabled while the index register is selected.
+const NMI_DISABLE: u8 = 0x80;
+
+/// Status register D: harmless to leave selected between accesses.
+const REG_STATUS_D: u8 = 0x0D;
+
+/// Claim the CMOS ports. Until this runs, `seconds_of_day` returns `None`.
+pub fn init() {
+    match ioport::claim(0x70, 2, "rtc") {
+        Ok(ports) => *CMOS_PORTS.lock() = Some(ports),
+        Err(e) => error!("rtc: cannot claim CMOS ports: {:?}", e),
+    }
+}
+
+/// Read a CMOS register with NMIs masked for the access only.
+fn read_register(ports: &IoPortRange, reg: u8) -> u8 {
+    ports.write(0, reg | NMI_DISABLE);
+    let value = ports.read::<u8>(1);
+    // Bit 7 of the index port is the NMI mask: clear it again on the way out.
+    ports.write(0, REG_STATUS_D);
+    value
+}
+
+/// Raw (hours, minutes, seconds) read once the update-in-progress flag clears.
+fn read_raw(ports: &IoPortRange) -> (u8, u8, u8) {
+    while read_register(ports, REG_STATUS_A) & 0x80 != 0 {
+        core::hint::spin_loop();
+    }
+    (
+        read_register(ports, REG_HOURS),
+        read_register(ports, REG_MINUTES),
+        read_register(ports, REG_SECONDS),
+    )
+}
+
+fn from_bcd(v: u8) -> u8 {
+    (v & 0x0F) + (v >> 4) * 10
+}
+
+/// Seconds since midnight according to the RTC.
+pub fn seconds_of_day() -> Option<u32> {
+    let ports = CMOS_PORTS.lock();
+    let ports = ports.as_ref()?;
+
+    // An update can land between register reads; retry until stable.
+    let mut raw = read_raw(ports);
+    loop {
+        let again = read_raw(ports);
+        if again == raw {
+            break;
+        }
+        raw = again;
+    }
+
+    let status_b = read_register(ports, REG_STATUS_B);
+    let (mut hours, mut minutes, mut seconds) = raw;
+    let pm = hours & 0x80 != 0;
+    hours &= 0x7F;
+
+    if status_b & 0x04 == 0 {
+        hours = from_bcd(hours);
+        minutes = from_bcd(minutes);
+        seconds = from_bcd(seconds);
+    }
+    if status_b & 0x02 == 0 {
+        // 12-hour mode: 12 AM is 0, 12 PM is 12.
+        hours = (hours % 12) + if pm { 12 } else { 0 };
+    }
+
+    Some(hours as u32 * 3600 + minutes as u32 * 60 + seconds as u32)
+}
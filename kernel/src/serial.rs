@@ -0,0 +1,174 @@
+//! 16550 UART driver and framed serial multiplexer (`serial.rs`).
+//!
+//! - `SerialPort` claims a UART's eight I/O ports and writes bytes with
+//!   transmit-ready polling.
+//! - The multiplexer runs on COM2 and carries several logical channels
+//!   (kernel log, trace events, GDB stub) over one line without interleaving.
+//!
+//! Frame layout (all multi-byte fields little-endian):
+//!
+//! ```text
+//! 0x7E | channel: u8 | len: u16 | payload: [u8; len] | crc: u16
+//! ```
+//!
+//! `crc` is CRC-16/CCITT-FALSE over the channel, both length bytes and the
+//! payload. Everything after the start marker is byte-stuffed HDLC-style:
+//! 0x7E and 0x7D are sent as 0x7D followed by the byte XOR 0x20, so 0x7E
+//! only ever starts a frame and a decoder resynchronizes on it even after a
+//! corrupted length. `len` counts payload bytes before stuffing.
+//! Payloads longer than `MAX_PAYLOAD` are split across frames. The host-side
+//! decoder lives in the runner crate (`cargo run --bin serial-demux`).
+
+use core::fmt;
+use spin::Mutex;
+
+use crate::ioport::{self, IoPortError, IoPortRange};
+
+/// Base port of COM1 (panic output / console).
+pub const COM1: u16 = 0x3F8;
+/// Base port of COM2 (multiplexed channels).
+pub const COM2: u16 = 0x2F8;
+
+/// Start-of-frame marker.
+pub const FRAME_START: u8 = 0x7E;
+/// Escape byte: the next byte is sent XORed with `ESCAPE_XOR`.
+pub const FRAME_ESCAPE: u8 = 0x7D;
+/// Applied to escaped bytes.
+pub const ESCAPE_XOR: u8 = 0x20;
+/// Largest payload carried by a single frame.
+pub const MAX_PAYLOAD: usize = 1024;
+
+/// Logical channels carried by the multiplexer.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// Formatted kernel log lines.
+    Log = 1,
+    /// Trace events.
+    Trace = 2,
+    /// GDB remote serial protocol.
+    Gdb = 3,
+}
+
+/// A polled 16550-compatible UART.
+pub struct SerialPort {
+    ports: IoPortRange,
+}
+
+// Register offsets from the UART base.
+const DATA: u16 = 0;
+const INT_ENABLE: u16 = 1;
+const FIFO_CTRL: u16 = 2;
+const LINE_CTRL: u16 = 3;
+const MODEM_CTRL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+impl SerialPort {
+    /// Claim and configure the UART at `base` for 38400 baud, 8N1, FIFOs on.
+    pub fn init(base: u16, owner: &'static str) -> Result<Self, IoPortError> {
+        let ports = ioport::claim(base, 8, owner)?;
+
+        ports.write(INT_ENABLE, 0x00u8); // no interrupts
+        ports.write(LINE_CTRL, 0x80u8);  // DLAB on
+        ports.write(DATA, 0x03u8);       // divisor low: 38400 baud
+        ports.write(INT_ENABLE, 0x00u8); // divisor high
+        ports.write(LINE_CTRL, 0x03u8);  // DLAB off, 8 bits, no parity, 1 stop
+        ports.write(FIFO_CTRL, 0xC7u8);  // enable + clear FIFOs, 14-byte threshold
+        ports.write(MODEM_CTRL, 0x0Bu8); // DTR, RTS, OUT2
+
+        Ok(SerialPort { ports })
+    }
+
+    /// Write one byte, waiting for the transmit holding register to empty.
+    pub fn write_byte(&self, byte: u8) {
+        while self.ports.read::<u8>(LINE_STATUS) & 0x20 == 0 {
+            core::hint::spin_loop();
+        }
+        self.ports.write(DATA, byte);
+    }
+
+    /// Write a byte slice.
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_byte(b);
+        }
+    }
+}
+
+/// COM2, once `mux_init` has run.
+static MUX: Mutex<Option<SerialPort>> = Mutex::new(None);
+
+/// Claim COM2 for the multiplexer. Until this runs, `mux_send` drops data.
+pub fn mux_init() {
+    if let Ok(port) = SerialPort::init(COM2, "serial-mux") {
+        *MUX.lock() = Some(port);
+    }
+}
+
+/// Send `payload` on `channel`, splitting it into frames as needed.
+pub fn mux_send(channel: Channel, payload: &[u8]) {
+    let mux = MUX.lock();
+    let Some(port) = mux.as_ref() else { return };
+
+    for chunk in payload.chunks(MAX_PAYLOAD) {
+        let len = (chunk.len() as u16).to_le_bytes();
+        let header = [channel as u8, len[0], len[1]];
+        let crc = crc16(crc16(CRC16_INIT, &header), chunk);
+
+        port.write_byte(FRAME_START);
+        for &b in header.iter().chain(chunk).chain(&crc.to_le_bytes()) {
+            write_stuffed(port, b);
+        }
+    }
+}
+
+/// Write a frame body byte, escaping the marker and escape bytes.
+fn write_stuffed(port: &SerialPort, byte: u8) {
+    if byte == FRAME_START || byte == FRAME_ESCAPE {
+        port.write_byte(FRAME_ESCAPE);
+        port.write_byte(byte ^ ESCAPE_XOR);
+    } else {
+        port.write_byte(byte);
+    }
+}
+
+/// Initial value of the frame CRC.
+pub const CRC16_INIT: u16 = 0xFFFF;
+
+/// Continue a CRC-16/CCITT-FALSE (polynomial 0x1021) over `bytes`.
+pub fn crc16(mut crc: u16, bytes: &[u8]) -> u16 {
+    for &b in bytes {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Fixed-capacity byte buffer implementing `fmt::Write`.
+/// Output beyond `N` bytes is silently truncated; never allocates.
+pub struct StackBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> StackBuf<N> {
+    pub const fn new() -> Self {
+        StackBuf { buf: [0; N], len: 0 }
+    }
+
+    /// Bytes written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl<const N: usize> fmt::Write for StackBuf<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(N - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
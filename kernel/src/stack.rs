@@ -1,5 +1,15 @@
 use crate::gdt::STACK_SIZE;
 use x86_64::VirtAddr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use log::{info, debug, warn};
+use crate::emergency;
+
+/// Byte pattern painted over IST stacks before first use.
+/// Bytes still holding it have never been touched by the CPU.
+pub const STACK_PAINT: u8 = 0xA5;
+
+/// Usage (percent of `STACK_SIZE`) above which a stack is reported as near overflow.
+pub const STACK_WARN_PERCENT: usize = 75;
 
 /// A 16‑byte aligned stack used for the double fault IST.
 /// Alignment is required by the x86_64 ABI for stack operations.
@@ -27,5 +37,135 @@ pub struct Stack(pub [u8; STACK_SIZE]);
 /// Global LAPIC IST stack.
 /// Used for LAPIC timer and page fault handlers to ensure
 /// reliable execution even if the main kernel stack is corrupted.
-pub static LAPIC_STACK: Stack = Stack([0; STACK_SIZE]);
+/// Mutable so it can be painted for high-water-mark tracking.
+pub static mut LAPIC_STACK: Stack = Stack([0; STACK_SIZE]);
+
+/// IST stacks whose usage is tracked.
+#[derive(Debug, Clone, Copy)]
+pub enum IstStack {
+    DoubleFault,
+    Lapic,
+}
+
+impl IstStack {
+    pub const ALL: [IstStack; 2] = [IstStack::DoubleFault, IstStack::Lapic];
+
+    /// Name used in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IstStack::DoubleFault => "double-fault IST",
+            IstStack::Lapic => "LAPIC IST",
+        }
+    }
+
+    /// Lowest address of the stack (the end it grows towards).
+    fn bottom(&self) -> *mut u8 {
+        unsafe {
+            match self {
+                IstStack::DoubleFault => core::ptr::addr_of_mut!(STACK.0) as *mut u8,
+                IstStack::Lapic => core::ptr::addr_of_mut!(LAPIC_STACK.0) as *mut u8,
+            }
+        }
+    }
 
+    /// Highest usage seen so far, as recorded by `check_usage`.
+    fn reported(&self) -> &'static AtomicUsize {
+        static REPORTED: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+        &REPORTED[*self as usize]
+    }
+}
+
+/// Fill every IST stack with `STACK_PAINT`.
+///
+/// Safety: must run before the stacks are first used (i.e. before interrupts
+/// are enabled) and after the LAPIC stack pages are mapped writable.
+pub unsafe fn paint_ist_stacks() {
+    for stack in IstStack::ALL {
+        core::ptr::write_bytes(stack.bottom(), STACK_PAINT, STACK_SIZE);
+    }
+    debug!("IST stacks painted with {:#x}", STACK_PAINT);
+}
+
+/// Deepest usage of `stack` in bytes since it was painted.
+///
+/// Scans up from the bottom for the first byte that no longer holds the paint.
+pub fn high_water_mark(stack: IstStack) -> usize {
+    let bottom = stack.bottom();
+    let untouched = (0..STACK_SIZE)
+        .take_while(|&i| unsafe { core::ptr::read_volatile(bottom.add(i)) } == STACK_PAINT)
+        .count();
+    STACK_SIZE - untouched
+}
+
+/// Log the high-water mark of every IST stack.
+pub fn report_usage() {
+    for stack in IstStack::ALL {
+        let used = high_water_mark(stack);
+        info!(
+            "{}: {} / {} bytes used ({}%)",
+            stack.name(),
+            used,
+            STACK_SIZE,
+            used * 100 / STACK_SIZE
+        );
+    }
+}
+
+/// Check for new high-water marks, warning when a stack nears overflow.
+/// Cheap enough to call from the periodic health check.
+pub fn check_usage() {
+    for stack in IstStack::ALL {
+        let used = high_water_mark(stack);
+        let previous = stack.reported().fetch_max(used, Ordering::Relaxed);
+        if used <= previous {
+            continue;
+        }
+
+        if used * 100 >= STACK_WARN_PERCENT * STACK_SIZE {
+            warn!("{} near overflow: {} / {} bytes used", stack.name(), used, STACK_SIZE);
+        } else {
+            debug!("{} new high-water mark: {} bytes", stack.name(), used);
+        }
+    }
+}
+
+
+/// Canary value checked by compiler-inserted stack-protector epilogues.
+/// Reseeded from the TSC by `init_stack_guard` early in boot.
+#[unsafe(no_mangle)]
+pub static mut __stack_chk_guard: u64 = 0x595E_9FBD_94FD_A766;
+
+/// Mix the TSC into the stack canary.
+///
+/// Must run before any protected frame that will later return: frames
+/// already on the stack hold the old value. `kernel_main` never returns,
+/// so calling this at its top is safe.
+pub fn init_stack_guard() {
+    let tsc = unsafe { core::arch::x86_64::_rdtsc() };
+    unsafe {
+        let guard = core::ptr::addr_of_mut!(__stack_chk_guard);
+        // Keep a zero low byte so string overruns cannot reproduce the canary.
+        *guard = (*guard ^ tsc.rotate_left(17) ^ tsc) & !0xFF;
+    }
+}
+
+/// Called by a protected function whose canary was overwritten.
+///
+/// Passes the return address (inside the corrupted function) on to
+/// `stack_chk_fail_at` so the report can name the offending frame.
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+pub extern "C" fn __stack_chk_fail() -> ! {
+    core::arch::naked_asm!("mov rdi, [rsp]", "jmp {}", sym stack_chk_fail_at)
+}
+
+/// Report a smashed stack via the emergency path and panic.
+///
+/// There is no in-kernel symbol table; resolve `rip` against the kernel
+/// ELF (e.g. `addr2line -f -e kernel <rip - image offset>`).
+extern "C" fn stack_chk_fail_at(rip: u64) -> ! {
+    emergency::print("STACK SMASHING DETECTED in function containing rip=");
+    emergency::print_hex(rip);
+    emergency::print("\n");
+    panic!("stack protector: canary overwritten near {:#x}", rip);
+}
@@ -0,0 +1,232 @@
+//! Kernel tunables (`sysctl.rs`).
+//!
+//! - A static registry of named knobs (`kernel.hostname`, `kernel.loglevel`, ...).
+//! - Each entry has a getter and, unless read-only, a validating setter that
+//!   applies the value to the owning subsystem immediately.
+//! - `get`/`set` look entries up by dotted name; `log_all` dumps the registry.
+//! - Writes require `CAP_SYS_ADMIN` in the calling process.
+//!
+//! The hostname is read on every `kernel.hostname` query and rarely written,
+//! so it lives in an `RcuCell` instead of behind a lock.
+//!
+//! Knobs that used to be hardcoded (log level, watchdog, health-check interval)
+//! now live behind this registry.
+
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::sync::atomic::Ordering;
+use log::{info, LevelFilter};
+
+use crate::logger::{FLOOD_PANIC, LOG_BURST};
+use crate::process::Capabilities;
+use crate::rcu::RcuCell;
+use crate::time::{HEALTH_INTERVAL, WATCHDOG_ENABLED};
+
+/// Default hostname until one is set.
+pub const DEFAULT_HOSTNAME: &str = "bulldog";
+
+/// Maximum hostname length in bytes (matches POSIX `HOST_NAME_MAX`).
+pub const HOSTNAME_MAX: usize = 64;
+
+lazy_static::lazy_static! {
+    /// Kernel hostname; read lock-free, replaced wholesale by `set_hostname`.
+    static ref HOSTNAME: RcuCell<String> = RcuCell::new(DEFAULT_HOSTNAME.to_string());
+}
+
+/// Value of a tunable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SysctlValue {
+    Int(u64),
+    Str(String),
+}
+
+impl fmt::Display for SysctlValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SysctlValue::Int(v) => write!(f, "{}", v),
+            SysctlValue::Str(s) => f.write_str(s),
+        }
+    }
+}
+
+/// Errors returned by `get`/`set`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SysctlError {
+    /// No tunable with that name.
+    UnknownName,
+    /// Wrong type or out of range.
+    InvalidValue,
+    /// The tunable cannot be written.
+    ReadOnly,
+    /// The caller lacks `CAP_SYS_ADMIN`.
+    PermissionDenied,
+}
+
+/// A registered tunable.
+pub struct Sysctl {
+    pub name: &'static str,
+    pub description: &'static str,
+    get: fn() -> SysctlValue,
+    set: Option<fn(&SysctlValue) -> Result<(), SysctlError>>,
+}
+
+/// The registry.
+static SYSCTLS: &[Sysctl] = &[
+    Sysctl {
+        name: "kernel.hostname",
+        description: "Name reported for this machine",
+        get: || SysctlValue::Str(hostname()),
+        set: Some(set_hostname_value),
+    },
+    Sysctl {
+        name: "kernel.loglevel",
+        description: "Maximum log level (0=off, 1=error .. 5=trace)",
+        get: || SysctlValue::Int(log::max_level() as u64),
+        set: Some(set_loglevel),
+    },
+    Sysctl {
+        name: "kernel.log_burst",
+        description: "Log messages per second allowed per source (0=unlimited)",
+        get: || SysctlValue::Int(LOG_BURST.load(Ordering::Relaxed)),
+        set: Some(set_log_burst),
+    },
+    Sysctl {
+        name: "kernel.log_flood_panic",
+        description: "Panic when a log source exceeds its burst (0=off, 1=on)",
+        get: || SysctlValue::Int(FLOOD_PANIC.load(Ordering::Relaxed) as u64),
+        set: Some(set_log_flood_panic),
+    },
+    Sysctl {
+        name: "kernel.watchdog",
+        description: "Idle-loop stall watchdog (0=off, 1=on)",
+        get: || SysctlValue::Int(WATCHDOG_ENABLED.load(Ordering::Relaxed) as u64),
+        set: Some(set_watchdog),
+    },
+    Sysctl {
+        name: "kernel.health_interval",
+        description: "Ticks between health-check log lines",
+        get: || SysctlValue::Int(HEALTH_INTERVAL.load(Ordering::Relaxed)),
+        set: Some(set_health_interval),
+    },
+    Sysctl {
+        name: "kernel.ticks",
+        description: "LAPIC timer ticks since boot",
+        get: || SysctlValue::Int(crate::time::get_ticks()),
+        set: None,
+    },
+    Sysctl {
+        name: "kernel.tick_hz",
+        description: "LAPIC tick rate (measured against the RTC once calibrated)",
+        get: || SysctlValue::Int(crate::time::tick_hz()),
+        set: None,
+    },
+];
+
+fn find(name: &str) -> Result<&'static Sysctl, SysctlError> {
+    SYSCTLS.iter().find(|s| s.name == name).ok_or(SysctlError::UnknownName)
+}
+
+/// Read a tunable by name.
+pub fn get(name: &str) -> Result<SysctlValue, SysctlError> {
+    Ok((find(name)?.get)())
+}
+
+/// Write a tunable by name, applying it immediately.
+pub fn set(name: &str, value: SysctlValue) -> Result<(), SysctlError> {
+    let sysctl = find(name)?;
+    let setter = sysctl.set.ok_or(SysctlError::ReadOnly)?;
+    if !crate::process::capable(Capabilities::SYS_ADMIN, "sysctl write") {
+        return Err(SysctlError::PermissionDenied);
+    }
+    setter(&value)?;
+    info!("sysctl: {} = {}", name, value);
+    Ok(())
+}
+
+/// Log every tunable with its current value.
+pub fn log_all() {
+    for sysctl in SYSCTLS {
+        info!("{} = {}    # {}", sysctl.name, (sysctl.get)(), sysctl.description);
+    }
+}
+
+/// Current kernel hostname.
+pub fn hostname() -> String {
+    HOSTNAME.read(|name| name.clone())
+}
+
+/// Set the kernel hostname (1..=`HOSTNAME_MAX` printable ASCII bytes, no spaces).
+pub fn set_hostname(name: &str) -> Result<(), SysctlError> {
+    let valid = !name.is_empty()
+        && name.len() <= HOSTNAME_MAX
+        && name.bytes().all(|b| b.is_ascii_graphic());
+    if !valid {
+        return Err(SysctlError::InvalidValue);
+    }
+    HOSTNAME.replace(name.to_string());
+    Ok(())
+}
+
+fn set_hostname_value(value: &SysctlValue) -> Result<(), SysctlError> {
+    match value {
+        SysctlValue::Str(name) => set_hostname(name),
+        SysctlValue::Int(_) => Err(SysctlError::InvalidValue),
+    }
+}
+
+fn set_loglevel(value: &SysctlValue) -> Result<(), SysctlError> {
+    let level = match value {
+        SysctlValue::Int(0) => LevelFilter::Off,
+        SysctlValue::Int(1) => LevelFilter::Error,
+        SysctlValue::Int(2) => LevelFilter::Warn,
+        SysctlValue::Int(3) => LevelFilter::Info,
+        SysctlValue::Int(4) => LevelFilter::Debug,
+        SysctlValue::Int(5) => LevelFilter::Trace,
+        _ => return Err(SysctlError::InvalidValue),
+    };
+    crate::logger::set_level(level);
+    Ok(())
+}
+
+fn set_log_burst(value: &SysctlValue) -> Result<(), SysctlError> {
+    match value {
+        SysctlValue::Int(v) => {
+            LOG_BURST.store(*v, Ordering::Relaxed);
+            Ok(())
+        }
+        SysctlValue::Str(_) => Err(SysctlError::InvalidValue),
+    }
+}
+
+fn set_log_flood_panic(value: &SysctlValue) -> Result<(), SysctlError> {
+    match value {
+        SysctlValue::Int(v @ (0 | 1)) => {
+            FLOOD_PANIC.store(*v == 1, Ordering::Relaxed);
+            Ok(())
+        }
+        _ => Err(SysctlError::InvalidValue),
+    }
+}
+
+fn set_watchdog(value: &SysctlValue) -> Result<(), SysctlError> {
+    match value {
+        SysctlValue::Int(v @ (0 | 1)) => {
+            WATCHDOG_ENABLED.store(*v == 1, Ordering::Relaxed);
+            Ok(())
+        }
+        _ => Err(SysctlError::InvalidValue),
+    }
+}
+
+fn set_health_interval(value: &SysctlValue) -> Result<(), SysctlError> {
+    match value {
+        SysctlValue::Int(v) if *v > 0 => {
+            HEALTH_INTERVAL.store(*v, Ordering::Relaxed);
+            crate::time::set_period(crate::time::HEALTH_TASK, *v);
+            Ok(())
+        }
+        _ => Err(SysctlError::InvalidValue),
+    }
+}
@@ -1,10 +1,17 @@
-use core::sync::atomic::{AtomicU64, Ordering};
-use log::info;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use log::{info, warn};
+use spin::Mutex;
 
 /// Global tick counter incremented by the LAPIC timer handler.
 /// Provides a simple heartbeat for the kernel.
 pub static TICKS: AtomicU64 = AtomicU64::new(0);
 
+/// Ticks between health-check log lines (`kernel.health_interval`).
+pub static HEALTH_INTERVAL: AtomicU64 = AtomicU64::new(1000);
+
+/// Whether the idle-loop watchdog may panic on stalls (`kernel.watchdog`).
+pub static WATCHDOG_ENABLED: AtomicBool = AtomicBool::new(true);
+
 /// Increment the global tick counter.
 /// Called on each LAPIC timer interrupt.
 pub fn tick() {
@@ -16,12 +23,114 @@ pub fn get_ticks() -> u64 {
     TICKS.load(Ordering::Relaxed)
 }
 
-/// Periodic health check.
-/// Logs a "proof of life" message every `interval` ticks.
-pub fn health_check(interval: u64) {
-    let t = get_ticks();
-    if t % interval == 0 {
-        info!("Health check: Kernel alive, ticks={}", t);
+/// Name of the health-check interval (`kernel.health_interval` retunes it).
+pub const HEALTH_TASK: &str = "health";
+
+/// Periodic health check, run as the `HEALTH_TASK` interval.
+/// Logs a "proof of life" message, checks IST stack high-water marks and
+/// reports log sources that were rate limited.
+pub fn health_check() {
+    info!("Health check: Kernel alive, ticks={}", get_ticks());
+    crate::logger::flush_suppressed();
+    crate::stack::check_usage();
+    #[cfg(feature = "heap-canary")]
+    crate::allocator::check_heap();
+}
+
+/// Maximum number of periodic tasks.
+const MAX_INTERVALS: usize = 16;
+
+/// Errors returned by `interval`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum IntervalError {
+    /// A period of zero ticks.
+    ZeroPeriod,
+    /// All `MAX_INTERVALS` slots are in use.
+    TableFull,
+}
+
+/// A periodic task scheduled against absolute deadlines.
+#[derive(Clone, Copy)]
+struct Interval {
+    name: &'static str,
+    period: u64,
+    /// Tick at which the callback is next due.
+    deadline: u64,
+    /// Periods skipped because the task fell more than a period behind.
+    overruns: u64,
+    callback: fn(),
+}
+
+static INTERVALS: Mutex<[Option<Interval>; MAX_INTERVALS]> = Mutex::new([None; MAX_INTERVALS]);
+
+/// Run `callback` every `period` ticks, starting one period from now.
+///
+/// Deadlines advance by exactly `period` each time, so handler latency does
+/// not accumulate as drift. A task that falls a full period or more behind
+/// skips the missed runs (counted as overruns) rather than firing in a burst.
+pub fn interval(name: &'static str, period: u64, callback: fn()) -> Result<(), IntervalError> {
+    if period == 0 {
+        return Err(IntervalError::ZeroPeriod);
+    }
+    let mut intervals = INTERVALS.lock();
+    let slot = intervals
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .ok_or(IntervalError::TableFull)?;
+    *slot = Some(Interval {
+        name,
+        period,
+        deadline: get_ticks() + period,
+        overruns: 0,
+        callback,
+    });
+    Ok(())
+}
+
+/// Change the period of the named task; its next deadline is rebased on now.
+/// Returns `false` if no such task exists or `period` is zero.
+pub fn set_period(name: &str, period: u64) -> bool {
+    if period == 0 {
+        return false;
+    }
+    let mut intervals = INTERVALS.lock();
+    match intervals.iter_mut().flatten().find(|i| i.name == name) {
+        Some(task) => {
+            task.period = period;
+            task.deadline = get_ticks() + period;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Run every task whose deadline has passed. Called from the idle loop.
+pub fn run_intervals() {
+    let now = get_ticks();
+    let mut due: [Option<fn()>; MAX_INTERVALS] = [None; MAX_INTERVALS];
+
+    {
+        let mut intervals = INTERVALS.lock();
+        for (task, slot) in intervals.iter_mut().zip(due.iter_mut()) {
+            let Some(task) = task else { continue };
+            if now < task.deadline {
+                continue;
+            }
+
+            task.deadline += task.period;
+            if now >= task.deadline {
+                let missed = (now - task.deadline) / task.period + 1;
+                task.deadline += missed * task.period;
+                task.overruns += missed;
+                warn!("time: interval '{}' skipped {} period(s)", task.name, missed);
+            }
+            *slot = Some(task.callback);
+        }
+    }
+
+    // Callbacks run unlocked so they may register or retune intervals.
+    for callback in due.iter().flatten() {
+        callback();
     }
 }
 
@@ -51,11 +160,15 @@ impl Watchdog {
         }
     }
 
-    /// Check kernel progress.
+    /// Check kernel progress (no-op while `WATCHDOG_ENABLED` is false).
     /// - If ticks have advanced within the window, reset failures.
     /// - If no progress, decrement grace or increment failures.
     /// - Panic only if failures exceed threshold after grace is exhausted.
     pub fn check(&mut self) {
+        if !WATCHDOG_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+
         let current = get_ticks();
 
         // Not yet at window boundary: do nothing.
@@ -87,3 +200,178 @@ impl Watchdog {
 
 
 
+
+/// Stop the named periodic task. Returns `false` if it was not scheduled.
+pub fn cancel_interval(name: &str) -> bool {
+    let mut intervals = INTERVALS.lock();
+    match intervals.iter_mut().find(|slot| slot.map_or(false, |i| i.name == name)) {
+        Some(slot) => {
+            *slot = None;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Tick rate assumed until calibration finishes (the timer is tuned for ~1 ms).
+pub const NOMINAL_TICK_HZ: u64 = 1000;
+
+/// RTC seconds observed during calibration (split into two halves).
+pub const CALIBRATION_SECS: u64 = 4;
+
+/// Deviation, in percent, beyond which a clock is reported as drifting.
+pub const DRIFT_PERCENT_MAX: u64 = 5;
+
+/// Ticks between RTC polls while calibrating.
+const CALIBRATION_POLL: u64 = 10;
+
+/// Name of the calibration interval.
+const CALIBRATION_TASK: &str = "timekeeping";
+
+/// Measured LAPIC tick rate (`NOMINAL_TICK_HZ` until calibrated).
+static TICK_HZ: AtomicU64 = AtomicU64::new(NOMINAL_TICK_HZ);
+
+/// Measured TSC rate (0 until calibrated).
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// LAPIC tick and TSC readings taken on an RTC second edge.
+#[derive(Clone, Copy)]
+struct Sample {
+    ticks: u64,
+    tsc: u64,
+}
+
+impl Sample {
+    fn now() -> Self {
+        Sample { ticks: get_ticks(), tsc: unsafe { core::arch::x86_64::_rdtsc() } }
+    }
+}
+
+/// In-progress calibration state.
+struct Calibration {
+    last_rtc: Option<u32>,
+    samples: [Option<Sample>; 3],
+    seconds: u64,
+}
+
+static CALIBRATION: Mutex<Calibration> = Mutex::new(Calibration {
+    last_rtc: None,
+    samples: [None; 3],
+    seconds: 0,
+});
+
+/// Current tick rate in Hz (measured once calibration completes).
+pub fn tick_hz() -> u64 {
+    TICK_HZ.load(Ordering::Relaxed)
+}
+
+/// Measured TSC rate in Hz, if calibration has completed.
+pub fn tsc_hz() -> Option<u64> {
+    match TSC_HZ.load(Ordering::Relaxed) {
+        0 => None,
+        hz => Some(hz),
+    }
+}
+
+/// Convert a tick count to milliseconds using the calibrated rate.
+pub fn ticks_to_ms(ticks: u64) -> u64 {
+    ticks.saturating_mul(1000) / tick_hz()
+}
+
+/// Convert milliseconds to ticks (rounded up, so timeouts never expire early).
+pub fn ms_to_ticks(ms: u64) -> u64 {
+    ms.saturating_mul(tick_hz()).div_ceil(1000)
+}
+
+/// Start calibrating the LAPIC tick and TSC against the RTC.
+///
+/// Runs in the background as an interval: samples are taken on RTC second
+/// edges, so the window is `CALIBRATION_SECS` whole seconds. Requires the
+/// timer to be running; results are logged by `calibrate_step`.
+pub fn calibrate() {
+    crate::rtc::init();
+    if let Err(e) = interval(CALIBRATION_TASK, CALIBRATION_POLL, calibrate_step) {
+        warn!("time: calibration not scheduled: {:?}", e);
+    }
+}
+
+fn calibrate_step() {
+    let Some(rtc) = crate::rtc::seconds_of_day() else {
+        cancel_interval(CALIBRATION_TASK);
+        warn!("time: RTC unavailable, keeping nominal {} Hz tick", NOMINAL_TICK_HZ);
+        return;
+    };
+
+    let mut cal = CALIBRATION.lock();
+    let edge = cal.last_rtc.map_or(false, |last| last != rtc);
+    cal.last_rtc = Some(rtc);
+    if !edge {
+        return;
+    }
+
+    // Samples at the first edge, the midpoint and the end of the window.
+    if cal.samples[0].is_none() {
+        cal.samples[0] = Some(Sample::now());
+        return;
+    }
+    cal.seconds += 1;
+    if cal.seconds == CALIBRATION_SECS / 2 {
+        cal.samples[1] = Some(Sample::now());
+    }
+    if cal.seconds < CALIBRATION_SECS {
+        return;
+    }
+    let (Some(start), Some(mid)) = (cal.samples[0], cal.samples[1]) else { return };
+    let end = Sample::now();
+    drop(cal);
+
+    cancel_interval(CALIBRATION_TASK);
+    report_calibration(start, mid, end);
+}
+
+/// Absolute difference between `a` and `b` as a percentage of `b`.
+fn deviation_percent(a: u64, b: u64) -> u64 {
+    a.abs_diff(b) * 100 / b.max(1)
+}
+
+fn report_calibration(start: Sample, mid: Sample, end: Sample) {
+    let half = CALIBRATION_SECS / 2;
+    let tick_hz = (end.ticks - start.ticks) / CALIBRATION_SECS;
+    let tsc_hz = (end.tsc - start.tsc) / CALIBRATION_SECS;
+
+    info!(
+        "time: calibrated over {} s: LAPIC {} Hz, TSC {} MHz",
+        CALIBRATION_SECS,
+        tick_hz,
+        tsc_hz / 1_000_000
+    );
+
+    // Each clock's rate in the first half vs the second, against the RTC.
+    let lapic_drift = deviation_percent(
+        (end.ticks - mid.ticks) / (CALIBRATION_SECS - half),
+        (mid.ticks - start.ticks) / half,
+    );
+    let tsc_drift = deviation_percent(
+        (end.tsc - mid.tsc) / (CALIBRATION_SECS - half),
+        (mid.tsc - start.tsc) / half,
+    );
+    if lapic_drift > DRIFT_PERCENT_MAX {
+        warn!("time: LAPIC tick rate drifted {}% against the RTC", lapic_drift);
+    }
+    if tsc_drift > DRIFT_PERCENT_MAX {
+        warn!("time: TSC rate drifted {}% against the RTC", tsc_drift);
+    }
+
+    let nominal_error = deviation_percent(tick_hz, NOMINAL_TICK_HZ);
+    if nominal_error > DRIFT_PERCENT_MAX {
+        warn!(
+            "time: tick rate is {}% off nominal {} Hz; tick-based timeouts now use {} Hz",
+            nominal_error, NOMINAL_TICK_HZ, tick_hz
+        );
+    }
+
+    if tick_hz > 0 {
+        TICK_HZ.store(tick_hz, Ordering::Relaxed);
+    }
+    TSC_HZ.store(tsc_hz, Ordering::Relaxed);
+}
@@ -43,6 +43,32 @@ pub struct TextWriter {
 }
 
 impl TextWriter {
+    /// Build a writer over raw framebuffer memory with the given geometry.
+    /// Line height follows the font rather than being fixed.
+    ///
+    /// # Safety
+    /// `ptr` must point to at least `pitch * height` bytes of mapped,
+    /// 4‑byte‑per‑pixel framebuffer memory that stays valid for `'static`.
+    pub unsafe fn from_raw(ptr: *mut u8, width: usize, height: usize, pitch: usize) -> Self {
+        let stride_pixels = pitch / 4;
+        let len = stride_pixels * height;
+        let framebuffer: &'static mut [u32] =
+            unsafe { core::slice::from_raw_parts_mut(ptr as *mut u32, len) };
+
+        TextWriter {
+            fg_color: (255, 255, 255),
+            bg_color: (0, 0, 0),
+            cursor_x: 0,
+            cursor_y: 0,
+            width,
+            height,
+            line_height: font_line_height(),
+            stride_pixels,
+            framebuffer,
+            enable_scroll: true,
+        }
+    }
+
     /// Log a message with level prefix and color.
     pub fn log(&mut self, level: LogLevel, args: Arguments) {
         self.set_log_level_color(level);
@@ -115,6 +141,60 @@ impl TextWriter {
         }
     }
 
+    /// Write raw bytes (e.g. a process's `write` buffer) without assuming UTF-8.
+    ///
+    /// Valid text is drawn as usual; `\r`, `\t` and backspace move the cursor,
+    /// other control characters are shown in caret notation (`^C`), and each
+    /// invalid sequence or unknown glyph is drawn as U+FFFD (or `?`).
+    /// Every byte is consumed, so the return value is always `bytes.len()`.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> usize {
+        for chunk in bytes.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                self.write_char_safe(c);
+            }
+            if !chunk.invalid().is_empty() {
+                self.write_replacement();
+            }
+        }
+        bytes.len()
+    }
+
+    /// `write_char` for untrusted input: no raw control characters reach the
+    /// glyph path, and nothing is silently dropped.
+    fn write_char_safe(&mut self, c: char) {
+        match c {
+            '\n' => self.write_char('\n'),
+            '\r' => self.cursor_x = 0,
+            '\t' => {
+                let stop = font_cell_width() * TAB_WIDTH;
+                loop {
+                    self.write_char(' ');
+                    if self.cursor_x % stop == 0 {
+                        break;
+                    }
+                }
+            }
+            '\x08' => self.cursor_x = self.cursor_x.saturating_sub(font_cell_width()),
+            '\0'..='\x1f' | '\x7f' => {
+                self.write_char('^');
+                self.write_char(((c as u8) ^ 0x40) as char);
+            }
+            c if c.is_control() => self.write_replacement(),
+            c if get_glyph(c).is_some() => self.write_char(c),
+            _ => self.write_replacement(),
+        }
+    }
+
+    /// Draw the replacement character, or `?` if the font lacks it.
+    fn write_replacement(&mut self) {
+        let c = if get_glyph(char::REPLACEMENT_CHARACTER).is_some() {
+            char::REPLACEMENT_CHARACTER
+        } else {
+            '?'
+        };
+        self.write_char(c);
+    }
+
     /// Set foreground and background colors.
     pub fn set_color(&mut self, fg: (u8, u8, u8), bg: (u8, u8, u8)) {
         self.fg_color = fg;
@@ -147,28 +227,23 @@ lazy_static::lazy_static! {
     pub static ref WRITER: Mutex<Option<TextWriter>> = Mutex::new(None);
 }
 
+/// Tab stops every this many cells in `write_bytes`.
+const TAB_WIDTH: usize = 8;
+
+/// Width of a text cell in pixels (glyph plus one pixel of spacing).
+pub fn font_cell_width() -> usize {
+    get_glyph('M').map(|g| g.width() + 1).unwrap_or(9)
+}
+
+/// Height of a text line in pixels, taken from the font.
+pub fn font_line_height() -> usize {
+    get_glyph('M').map(|g| g.height()).unwrap_or(16)
+}
+
 /// Initialize the global WRITER from a KernelFramebuffer.
 /// Maps the framebuffer pointer into a slice and constructs TextWriter.
 pub fn framebuffer_init(fb: &mut KernelFramebuffer) {
-    let stride_pixels = fb.pitch / 4;
-    let len = stride_pixels * fb.height;
-    let framebuffer: &'static mut [u32] = unsafe {
-        core::slice::from_raw_parts_mut(fb.ptr as *mut u32, len)
-    };
-
-    let writer = TextWriter {
-        fg_color: (255, 255, 255),
-        bg_color: (0, 0, 0),
-        cursor_x: 0,
-        cursor_y: 0,
-        width: fb.width,
-        height: fb.height,
-        line_height: 16, // must match font height
-        stride_pixels,
-        framebuffer,
-        enable_scroll: true,
-    };
-
+    let writer = unsafe { TextWriter::from_raw(fb.ptr, fb.width, fb.height, fb.pitch) };
     WRITER.lock().replace(writer);
 }
 
@@ -0,0 +1,26 @@
+#!/usr/bin/env bash
+# Verify the kernel is built with stack-protector canaries.
+# Builds the kernel for x86_64-unknown-none (flags from .cargo/config.toml)
+# and counts call sites of `__stack_chk_fail` in its disassembly.
+
+set -euo pipefail
+
+RED="\033[0;31m"
+GREEN="\033[0;32m"
+NC="\033[0m"
+
+cd "$(dirname "$0")/.."
+
+cargo +nightly build -Z bindeps -p kernel --bin kernel --target x86_64-unknown-none
+
+KERNEL="target/x86_64-unknown-none/debug/kernel"
+OBJDUMP="$(command -v llvm-objdump || command -v objdump)"
+
+CALLS="$("$OBJDUMP" -d "$KERNEL" | grep -c 'call.*<__stack_chk_fail>' || true)"
+
+if [ "$CALLS" -eq 0 ]; then
+  echo -e "${RED}✖ No __stack_chk_fail call sites in ${KERNEL}: canaries are not being emitted.${NC}"
+  echo -e "${RED}  Check the [target.x86_64-unknown-none] rustflags in .cargo/config.toml.${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✔ ${CALLS} __stack_chk_fail call site(s) in ${KERNEL}.${NC}"
@@ -0,0 +1,47 @@
+#!/usr/bin/env bash
+# Panic self-test: boot a kernel built with `panic-selftest`, which poisons
+# the heap (garbage + allocator locked forever) and then panics, and check
+# that the panic report still reaches COM1. A panic path that allocates or
+# takes the heap lock hangs instead and fails the test on timeout.
+
+set -euo pipefail
+
+RED="\033[0;31m"
+GREEN="\033[0;32m"
+NC="\033[0m"
+
+TIMEOUT="${TIMEOUT:-120}"
+EXPECTED="KERNEL PANIC at .*: panic-selftest: heap poisoned"
+
+cd "$(dirname "$0")/.."
+
+cargo +nightly build -Z bindeps --features panic-selftest
+
+LOG="$(mktemp)"
+trap 'rm -f "$LOG"' EXIT
+
+# COM1 is on stdio; the kernel halts after panicking, so stop QEMU once the
+# report shows up (or the timeout expires).
+set -m # run the runner in its own process group
+BULLDOG_HEADLESS=1 cargo +nightly run -Z bindeps --features panic-selftest >"$LOG" 2>&1 &
+RUNNER=$!
+set +m
+
+for _ in $(seq "$TIMEOUT"); do
+  if grep -q "$EXPECTED" "$LOG"; then
+    break
+  fi
+  sleep 1
+done
+# Signal the runner's whole process group: this takes QEMU down too.
+kill -- -"$RUNNER" 2>/dev/null || true
+wait "$RUNNER" 2>/dev/null || true
+
+if grep -q "$EXPECTED" "$LOG"; then
+  echo -e "${GREEN}✔ Panic report printed with the heap poisoned:${NC}"
+  grep "KERNEL PANIC" "$LOG"
+else
+  echo -e "${RED}✖ No panic report within ${TIMEOUT}s. Serial output:${NC}"
+  tail -n 40 "$LOG"
+  exit 1
+fi
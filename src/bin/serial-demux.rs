@@ -0,0 +1,215 @@
+//! Host-side decoder for Bulldog's serial multiplexer (COM2).
+//!
+//! Usage:
+//!   cargo run --bin serial-demux                   # connect to 127.0.0.1:4555 (runner default)
+//!   cargo run --bin serial-demux -- <host:port>    # connect to another QEMU chardev socket
+//!   cargo run --bin serial-demux -- <file>         # decode a captured stream
+//!
+//! Each frame is `0x7E | channel | len (u16 LE) | payload | crc16 (LE)`, with
+//! everything after the marker byte-stuffed (0x7E/0x7D sent as 0x7D, b ^ 0x20).
+//! Log payloads are printed as text; trace and GDB payloads are printed as hex.
+//! A start marker always begins a new frame, so corrupt or truncated frames
+//! are dropped and decoding resumes with the next one.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::net::{SocketAddr, TcpStream};
+
+const FRAME_START: u8 = 0x7E;
+const FRAME_ESCAPE: u8 = 0x7D;
+const ESCAPE_XOR: u8 = 0x20;
+const CRC16_INIT: u16 = 0xFFFF;
+
+/// Channel, length and CRC bytes around the payload.
+const HEADER_LEN: usize = 3;
+const CRC_LEN: usize = 2;
+
+/// CRC-16/CCITT-FALSE, as computed by the kernel's `serial::crc16`.
+fn crc16(mut crc: u16, bytes: &[u8]) -> u16 {
+    for &b in bytes {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Frame {
+    channel: u8,
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Dropped {
+    /// The frame was complete but its CRC did not match.
+    BadCrc { channel: u8 },
+    /// A start marker arrived before the frame was complete.
+    Truncated,
+}
+
+/// Byte-at-a-time frame decoder.
+#[derive(Default)]
+struct Decoder {
+    /// Unstuffed bytes of the frame in progress; `None` between frames.
+    body: Option<Vec<u8>>,
+    escaped: bool,
+}
+
+impl Decoder {
+    fn push(&mut self, byte: u8) -> Option<Result<Frame, Dropped>> {
+        if byte == FRAME_START {
+            let partial = self.body.replace(Vec::new());
+            self.escaped = false;
+            return partial.filter(|b| !b.is_empty()).map(|_| Err(Dropped::Truncated));
+        }
+        let body = self.body.as_mut()?;
+        if byte == FRAME_ESCAPE {
+            self.escaped = true;
+            return None;
+        }
+        body.push(if self.escaped { byte ^ ESCAPE_XOR } else { byte });
+        self.escaped = false;
+
+        if body.len() < HEADER_LEN {
+            return None;
+        }
+        let len = u16::from_le_bytes([body[1], body[2]]) as usize;
+        if body.len() < HEADER_LEN + len + CRC_LEN {
+            return None;
+        }
+
+        let body = self.body.take().unwrap();
+        let (data, crc) = body.split_at(HEADER_LEN + len);
+        let channel = data[0];
+        if crc16(CRC16_INIT, data) != u16::from_le_bytes([crc[0], crc[1]]) {
+            return Some(Err(Dropped::BadCrc { channel }));
+        }
+        Some(Ok(Frame { channel, payload: data[HEADER_LEN..].to_vec() }))
+    }
+}
+
+fn main() -> io::Result<()> {
+    let target = env::args().nth(1).unwrap_or_else(|| "127.0.0.1:4555".to_string());
+
+    let input: Box<dyn Read> = match target.parse::<SocketAddr>() {
+        Ok(addr) => {
+            eprintln!("serial-demux: connecting to {addr}");
+            Box::new(TcpStream::connect(addr)?)
+        }
+        Err(_) => Box::new(File::open(&target)?),
+    };
+
+    let mut decoder = Decoder::default();
+    for byte in BufReader::new(input).bytes() {
+        match decoder.push(byte?) {
+            None => {}
+            Some(Ok(frame)) => print_frame(&frame),
+            Some(Err(Dropped::BadCrc { channel })) => {
+                eprintln!("serial-demux: dropped frame on channel {channel} (bad CRC)")
+            }
+            Some(Err(Dropped::Truncated)) => eprintln!("serial-demux: dropped truncated frame"),
+        }
+    }
+
+    Ok(())
+}
+
+fn print_frame(frame: &Frame) {
+    match frame.channel {
+        1 => print!("[log]   {}", String::from_utf8_lossy(&frame.payload)),
+        2 => println!("[trace] {}", hex(&frame.payload)),
+        3 => println!("[gdb]   {}", hex(&frame.payload)),
+        other => println!("[ch{other}]   {}", hex(&frame.payload)),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a frame the way the kernel's `serial::mux_send` does.
+    fn encode(channel: u8, payload: &[u8]) -> Vec<u8> {
+        let len = (payload.len() as u16).to_le_bytes();
+        let header = [channel, len[0], len[1]];
+        let crc = crc16(crc16(CRC16_INIT, &header), payload);
+
+        let mut out = vec![FRAME_START];
+        for &b in header.iter().chain(payload).chain(&crc.to_le_bytes()) {
+            if b == FRAME_START || b == FRAME_ESCAPE {
+                out.extend([FRAME_ESCAPE, b ^ ESCAPE_XOR]);
+            } else {
+                out.push(b);
+            }
+        }
+        out
+    }
+
+    fn decode(stream: &[u8]) -> Vec<Result<Frame, Dropped>> {
+        let mut decoder = Decoder::default();
+        stream.iter().filter_map(|&b| decoder.push(b)).collect()
+    }
+
+    #[test]
+    fn crc_matches_ccitt_false_check_value() {
+        assert_eq!(crc16(CRC16_INIT, b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn payload_containing_marker_and_escape_round_trips() {
+        let payload = [0x7E, 0x41, 0x7D, 0x7E, 0x7E, 0x00, 0x7D];
+        let mut stream = encode(1, &payload);
+        stream.extend(encode(2, b"next"));
+
+        assert_eq!(
+            decode(&stream),
+            vec![
+                Ok(Frame { channel: 1, payload: payload.to_vec() }),
+                Ok(Frame { channel: 2, payload: b"next".to_vec() }),
+            ]
+        );
+    }
+
+    #[test]
+    fn length_of_0x7e_is_escaped() {
+        let payload = vec![0xAB; 0x7E];
+        assert_eq!(decode(&encode(3, &payload)), vec![Ok(Frame { channel: 3, payload })]);
+    }
+
+    #[test]
+    fn corrupted_length_resyncs_on_next_frame() {
+        let mut stream = encode(1, b"hello");
+        stream[2] = 0xFF; // length low byte: now far longer than the frame
+        stream.extend(encode(1, b"world"));
+
+        assert_eq!(
+            decode(&stream),
+            vec![Err(Dropped::Truncated), Ok(Frame { channel: 1, payload: b"world".to_vec() })]
+        );
+    }
+
+    #[test]
+    fn corrupted_payload_fails_crc() {
+        let mut stream = encode(1, b"hello");
+        stream[5] ^= 0x01;
+        stream.extend(encode(1, b"world"));
+
+        assert_eq!(
+            decode(&stream),
+            vec![Err(Dropped::BadCrc { channel: 1 }), Ok(Frame { channel: 1, payload: b"world".to_vec() })]
+        );
+    }
+
+    #[test]
+    fn noise_before_first_marker_is_ignored() {
+        let mut stream = vec![0x00, 0x7D, 0x41];
+        stream.extend(encode(2, b"x"));
+        assert_eq!(decode(&stream), vec![Ok(Frame { channel: 2, payload: b"x".to_vec() })]);
+    }
+}
@@ -18,7 +18,13 @@ fn main() {
     cmd.arg("-smp").arg("2");
     cmd.arg("-m").arg("512M");
     cmd.arg("-serial").arg("stdio");
+    // COM2 carries the kernel's framed multiplexer; decode with `cargo run --bin serial-demux`.
+    cmd.arg("-serial").arg("tcp:127.0.0.1:4555,server=on,wait=off");
     cmd.arg("-global").arg("kvm-pit.lost_tick_policy=discard");
+    // Scripted runs (e.g. scripts/panic-selftest.sh) have no display.
+    if env::var_os("BULLDOG_HEADLESS").is_some() {
+        cmd.arg("-display").arg("none");
+    }
 
     if uefi {
         cmd.arg("-bios").arg(ovmf_prebuilt::ovmf_pure_efi());